@@ -0,0 +1,441 @@
+//! A blocking Client API, for use without an async runtime, such as in command-line tools and build scripts.
+//!
+//! It wraps the async [`Client`](super::Client), driving it on a runtime of its own, so requests are retried and bodies resumed just the same. The [`Response`] body is read with [`io::Read`].
+//!
+//! See [`reqwest::blocking`](https://docs.rs/reqwest/0.10/reqwest/blocking/index.html).
+//!
+//! # Panics
+//!
+//! As with `reqwest::blocking`, the methods that send a request or read a body panic if called from within an async runtime.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::BufRead;
+//!
+//! let body = reqwest_resume::blocking::get("http://example.com/big.txt").unwrap();
+//! for line in body.lines() {
+//!     println!("{}", line.unwrap());
+//! }
+//! ```
+
+use bytes::{Buf, Bytes, BytesMut};
+use std::{
+	convert::TryFrom, fmt, io, mem, net::SocketAddr, ops::RangeBounds, sync::Arc, time::Duration
+};
+
+use super::{reader::Failed, Checksum, Codec, Event, Result, RetryPolicy, Stats};
+
+/// A `Client` to make Requests with, blocking until they complete.
+///
+/// See [`reqwest_resume::Client`](super::Client).
+#[derive(Clone, Debug)]
+pub struct Client {
+	client: super::Client,
+	runtime: Arc<tokio::runtime::Runtime>,
+}
+impl Client {
+	/// Constructs a new `Client`.
+	///
+	/// See [`reqwest_resume::Client::new()`](super::Client::new).
+	pub fn new() -> Self {
+		super::Client::new().into()
+	}
+	/// Set the [`RetryPolicy`] used by requests made with this `Client`.
+	pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.client = self.client.retry_policy(retry_policy);
+		self
+	}
+	/// Set a function to be called with each [`Event`] in the progress of requests made with this `Client`.
+	pub fn observer<F>(mut self, observer: F) -> Self
+	where
+		F: Fn(&Event) + Send + Sync + 'static,
+	{
+		self.client = self.client.observer(observer);
+		self
+	}
+	/// Convenience method to make a `GET` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::get()`](super::Client::get).
+	pub fn get<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::GET, url)
+	}
+	/// Convenience method to make a `HEAD` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::head()`](super::Client::head).
+	pub fn head<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::HEAD, url)
+	}
+	/// Convenience method to make a `POST` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::post()`](super::Client::post).
+	pub fn post<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::POST, url)
+	}
+	/// Convenience method to make a `PUT` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::put()`](super::Client::put).
+	pub fn put<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::PUT, url)
+	}
+	/// Convenience method to make a `PATCH` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::patch()`](super::Client::patch).
+	pub fn patch<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::PATCH, url)
+	}
+	/// Convenience method to make a `DELETE` request to a URL.
+	///
+	/// See [`reqwest_resume::Client::delete()`](super::Client::delete).
+	pub fn delete<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::DELETE, url)
+	}
+	/// Start building a `Request` with the `Method` and `Url`.
+	///
+	/// See [`reqwest_resume::Client::request()`](super::Client::request).
+	pub fn request<U>(&self, method: reqwest::Method, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		RequestBuilder {
+			request: self.client.request(method, url),
+			runtime: self.runtime.clone(),
+		}
+	}
+}
+impl From<super::Client> for Client {
+	/// Wrap an async [`Client`](super::Client), for example one made from a configured [`reqwest::Client`] with [`ClientExt::resumable()`](super::ClientExt::resumable).
+	fn from(client: super::Client) -> Self {
+		let runtime = tokio::runtime::Builder::new()
+			.threaded_scheduler()
+			.core_threads(1)
+			.thread_name("reqwest_resume-blocking")
+			.enable_all()
+			.build()
+			.expect("failed to create runtime");
+		Self {
+			client,
+			runtime: Arc::new(runtime),
+		}
+	}
+}
+
+/// A builder to construct the properties of a Request.
+///
+/// See [`reqwest_resume::RequestBuilder`](super::RequestBuilder).
+#[derive(Debug)]
+pub struct RequestBuilder {
+	request: super::RequestBuilder,
+	runtime: Arc<tokio::runtime::Runtime>,
+}
+impl RequestBuilder {
+	/// Set the [`RetryPolicy`] used by this request, overriding that of the [`Client`].
+	pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.request = self.request.retry_policy(retry_policy);
+		self
+	}
+	/// Set a function to be called with each [`Event`] in the progress of this request, overriding that of the [`Client`].
+	pub fn observer<F>(mut self, observer: F) -> Self
+	where
+		F: Fn(&Event) + Send + Sync + 'static,
+	{
+		self.request = self.request.observer(observer);
+		self
+	}
+	/// Add a header to the request.
+	///
+	/// See [`reqwest_resume::RequestBuilder::header()`](super::RequestBuilder::header).
+	pub fn header<K, V>(mut self, key: K, value: V) -> Self
+	where
+		reqwest::header::HeaderName: TryFrom<K>,
+		<reqwest::header::HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+		reqwest::header::HeaderValue: TryFrom<V>,
+		<reqwest::header::HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+	{
+		self.request = self.request.header(key, value);
+		self
+	}
+	/// Add a set of headers to the request, replacing any already set with the same names.
+	///
+	/// See [`reqwest_resume::RequestBuilder::headers()`](super::RequestBuilder::headers).
+	pub fn headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+		self.request = self.request.headers(headers);
+		self
+	}
+	/// Enable HTTP basic authentication.
+	///
+	/// See [`reqwest_resume::RequestBuilder::basic_auth()`](super::RequestBuilder::basic_auth).
+	pub fn basic_auth<U, P>(mut self, username: U, password: Option<P>) -> Self
+	where
+		U: fmt::Display,
+		P: fmt::Display,
+	{
+		self.request = self.request.basic_auth(username, password);
+		self
+	}
+	/// Enable HTTP bearer authentication.
+	///
+	/// See [`reqwest_resume::RequestBuilder::bearer_auth()`](super::RequestBuilder::bearer_auth).
+	pub fn bearer_auth<T>(mut self, token: T) -> Self
+	where
+		T: fmt::Display,
+	{
+		self.request = self.request.bearer_auth(token);
+		self
+	}
+	/// Modify the query string of the URL.
+	///
+	/// See [`reqwest_resume::RequestBuilder::query()`](super::RequestBuilder::query).
+	pub fn query<T>(mut self, query: &T) -> Self
+	where
+		T: serde::Serialize + ?Sized,
+	{
+		self.request = self.request.query(query);
+		self
+	}
+	/// Set a timeout for each attempt, from when it starts connecting until its body has finished.
+	///
+	/// See [`reqwest_resume::RequestBuilder::timeout()`](super::RequestBuilder::timeout).
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.request = self.request.timeout(timeout);
+		self
+	}
+	/// Set the request body.
+	///
+	/// See [`reqwest_resume::RequestBuilder::body()`](super::RequestBuilder::body).
+	pub fn body<T>(mut self, body: T) -> Self
+	where
+		T: Into<Bytes>,
+	{
+		self.request = self.request.body(body);
+		self
+	}
+	/// Set whether the request may be retried and resumed, overriding the default of doing so only for idempotent methods.
+	///
+	/// See [`reqwest_resume::RequestBuilder::idempotent()`](super::RequestBuilder::idempotent).
+	pub fn idempotent(mut self, idempotent: bool) -> Self {
+		self.request = self.request.idempotent(idempotent);
+		self
+	}
+	/// Send an `Idempotency-Key` header with `key`, and allow the request to be retried and resumed.
+	///
+	/// See [`reqwest_resume::RequestBuilder::idempotency_key()`](super::RequestBuilder::idempotency_key).
+	pub fn idempotency_key(mut self, key: &str) -> Self {
+		self.request = self.request.idempotency_key(key);
+		self
+	}
+	/// Request only a range of the body, for example `start..end` or `start..`.
+	///
+	/// See [`reqwest_resume::RequestBuilder::range()`](super::RequestBuilder::range).
+	///
+	/// # Panics
+	///
+	/// Panics if the range is empty.
+	pub fn range<R>(mut self, range: R) -> Self
+	where
+		R: RangeBounds<u64>,
+	{
+		self.request = self.request.range(range);
+		self
+	}
+	/// Request only the last `n` bytes of the body.
+	///
+	/// See [`reqwest_resume::RequestBuilder::suffix()`](super::RequestBuilder::suffix).
+	pub fn suffix(mut self, n: u64) -> Self {
+		self.request = self.request.suffix(n);
+		self
+	}
+	/// Verify the body against `checksum` once it's complete.
+	///
+	/// See [`reqwest_resume::RequestBuilder::checksum()`](super::RequestBuilder::checksum).
+	pub fn checksum(mut self, checksum: Checksum) -> Self {
+		self.request = self.request.checksum(checksum);
+		self
+	}
+	/// Decompress the body if the server compresses it.
+	///
+	/// See [`reqwest_resume::RequestBuilder::decompress()`](super::RequestBuilder::decompress).
+	pub fn decompress(mut self, decompress: bool) -> Self {
+		self.request = self.request.decompress(decompress);
+		self
+	}
+	/// Constructs the Request and sends it the target URL, blocking until the initial response is received.
+	///
+	/// See [`reqwest_resume::RequestBuilder::send()`](super::RequestBuilder::send).
+	pub fn send(self) -> Result<Response> {
+		let Self {
+			mut request,
+			runtime,
+		} = self;
+		let response = runtime.handle().block_on(request.send())?;
+		Ok(Response {
+			response,
+			chunk: Bytes::new(),
+			failed: None,
+			runtime,
+		})
+	}
+}
+
+/// A Response to a submitted Request, whose body is read by blocking.
+///
+/// The body is resumed just as that of an async [`Response`](super::Response). Reading it with [`io::Read`] or [`io::BufRead`] returns errors as an [`io::Error`] wrapping the [`reqwest_resume::Error`](super::Error), and fails every later read too, as [`BodyReader`](super::BodyReader) does.
+///
+/// See [`reqwest_resume::Response`](super::Response).
+#[derive(Debug)]
+pub struct Response {
+	response: super::Response,
+	chunk: Bytes,
+	failed: Option<Failed>,
+	runtime: Arc<tokio::runtime::Runtime>,
+}
+impl Response {
+	/// Get the `StatusCode` of this `Response`.
+	pub fn status(&self) -> reqwest::StatusCode {
+		self.response.status()
+	}
+	/// Get the `Headers` of this `Response`.
+	pub fn headers(&self) -> &reqwest::header::HeaderMap {
+		self.response.headers()
+	}
+	/// Get the content-length of this response, if known.
+	pub fn content_length(&self) -> Option<u64> {
+		self.response.content_length()
+	}
+	/// Get the final `Url` of this `Response`, after any redirects.
+	pub fn url(&self) -> &reqwest::Url {
+		self.response.url()
+	}
+	/// Get the remote address used to get this `Response`.
+	pub fn remote_addr(&self) -> Option<SocketAddr> {
+		self.response.remote_addr()
+	}
+	/// A snapshot of the [`Stats`] of this response so far.
+	pub fn stats(&self) -> Stats {
+		self.response.stats()
+	}
+	/// Decompress the body with `codec`. This must be done before any of the body is read.
+	///
+	/// See [`reqwest_resume::Response::decompressed()`](super::Response::decompressed).
+	pub fn decompressed(mut self, codec: Codec) -> Self {
+		self.response = self.response.decompressed(codec);
+		self
+	}
+	/// Decompress the body with the [`Codec`] detected from its first bytes, or otherwise from the extension of the URL. This must be done before any of the body is read.
+	///
+	/// See [`reqwest_resume::Response::decompressed_auto()`](super::Response::decompressed_auto).
+	pub fn decompressed_auto(mut self) -> Self {
+		self.response = self.response.decompressed_auto();
+		self
+	}
+	/// Get a chunk of the response body, or `None` once it's complete.
+	///
+	/// See [`reqwest_resume::Response::chunk()`](super::Response::chunk).
+	pub fn chunk(&mut self) -> Result<Option<Bytes>> {
+		if !self.chunk.is_empty() {
+			return Ok(Some(mem::take(&mut self.chunk)));
+		}
+		self.runtime.handle().block_on(self.response.chunk())
+	}
+	/// Get the rest of the response body as `Bytes`.
+	///
+	/// See [`reqwest_resume::Response::bytes()`](super::Response::bytes).
+	pub fn bytes(mut self) -> Result<Bytes> {
+		let mut body = BytesMut::new();
+		while let Some(chunk) = self.chunk()? {
+			body.extend_from_slice(&chunk);
+		}
+		Ok(body.freeze())
+	}
+	/// Get the rest of the response text, decoded per the `charset` of the `Content-Type` header, defaulting to UTF-8.
+	///
+	/// See [`reqwest_resume::Response::text()`](super::Response::text).
+	pub fn text(self) -> Result<String> {
+		let encoding = self.response.encoding();
+		Ok(encoding.decode(&self.bytes()?).0.into_owned())
+	}
+	/// Try to deserialize the rest of the response body as JSON.
+	///
+	/// See [`reqwest_resume::Response::json()`](super::Response::json).
+	#[cfg(feature = "json")]
+	pub fn json<T>(self) -> Result<T>
+	where
+		T: serde::de::DeserializeOwned,
+	{
+		Ok(serde_json::from_slice(&self.bytes()?)?)
+	}
+	/// Copy the rest of the response body into `writer`, returning the number of bytes copied.
+	///
+	/// Unlike with [`io::copy()`], an error reading the body is returned as the [`reqwest_resume::Error`](super::Error) itself.
+	pub fn copy_to<W>(&mut self, writer: &mut W) -> Result<u64>
+	where
+		W: io::Write + ?Sized,
+	{
+		let mut copied = 0;
+		while let Some(chunk) = self.chunk()? {
+			writer.write_all(&chunk)?;
+			copied += chunk.len() as u64;
+		}
+		Ok(copied)
+	}
+}
+
+impl io::Read for Response {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let chunk = io::BufRead::fill_buf(self)?;
+		let n = chunk.len().min(buf.len());
+		buf[..n].copy_from_slice(&chunk[..n]);
+		self.chunk.advance(n);
+		Ok(n)
+	}
+}
+impl io::BufRead for Response {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		if let Some(failed) = &self.failed {
+			return Err(failed.error());
+		}
+		while self.chunk.is_empty() {
+			match self.runtime.handle().block_on(self.response.chunk()) {
+				Ok(Some(chunk)) => self.chunk = chunk,
+				Ok(None) => break,
+				Err(err) => {
+					let err = io::Error::from(err);
+					self.failed = Some(Failed::new(&err));
+					return Err(err);
+				}
+			}
+		}
+		Ok(&self.chunk)
+	}
+	fn consume(&mut self, amt: usize) {
+		self.chunk.advance(amt);
+	}
+}
+
+/// Shortcut method to quickly make a blocking GET request.
+///
+/// See [`reqwest_resume::get`](super::get).
+pub fn get<U>(url: U) -> Result<Response>
+where
+	U: reqwest::IntoUrl,
+{
+	Client::new().get(url).send()
+}
@@ -0,0 +1,119 @@
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::{convert::TryInto, fmt};
+
+use super::{Error, RequestBuilder, Result};
+
+impl RequestBuilder {
+	/// Verify the body against `checksum` once it's complete, failing with [`Error::ChecksumMismatch`] if it doesn't match.
+	///
+	/// The checksum is computed across every resume, over just the bytes delivered. It's of the whole representation, so isn't verified if a range was requested.
+	pub fn checksum(mut self, checksum: Checksum) -> Self {
+		self.checksum = Some(checksum);
+		self
+	}
+}
+
+/// A checksum to verify a body against, with [`RequestBuilder::checksum()`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Checksum {
+	/// The checksum given in the headers of the initial response: the SHA-256 or MD5 digest from `Repr-Digest` or `Digest`, otherwise `Content-MD5`. If there isn't one, the body isn't verified.
+	Headers,
+	/// The MD5 digest in the `ETag` of the initial response, as S3 gives for objects uploaded in a single part without SSE-KMS or SSE-C encryption. Other servers' entity tags are opaque, so only use this with S3 or a server known to do the same. If the `ETag` isn't an MD5 digest, the body isn't verified.
+	S3Etag,
+	/// An MD5 digest.
+	Md5([u8; 16]),
+	/// A SHA-256 digest.
+	Sha256([u8; 32]),
+}
+impl Checksum {
+	/// Resolve [`Checksum::Headers`] and [`Checksum::S3Etag`] against the headers of the initial response.
+	fn resolve(self, headers: &reqwest::header::HeaderMap) -> Option<Self> {
+		let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+		match self {
+			Self::Headers => (),
+			Self::S3Etag => {
+				let etag = header("etag")?.trim_matches('"');
+				let mut digest = [0; 16];
+				hex::decode_to_slice(etag, &mut digest).ok()?;
+				return Some(Self::Md5(digest));
+			}
+			checksum => return Some(checksum),
+		}
+		let digests = header("repr-digest")
+			.into_iter()
+			.chain(header("digest"))
+			.flat_map(|value| value.split(','))
+			.filter_map(|digest| {
+				let (algorithm, digest) = digest.trim().split_once('=')?;
+				// Repr-Digest wraps the base64 in colons
+				let digest = base64::decode(digest.trim_matches(':')).ok()?;
+				Some((algorithm.to_ascii_lowercase(), digest))
+			})
+			.collect::<Vec<_>>();
+		let digest = |algorithm| {
+			digests
+				.iter()
+				.find(|(name, _)| name == algorithm)
+				.map(|(_, digest)| digest.as_slice())
+		};
+		None.or_else(|| Some(Self::Sha256(digest("sha-256")?.try_into().ok()?)))
+			.or_else(|| Some(Self::Md5(digest("md5")?.try_into().ok()?)))
+			.or_else(|| {
+				let digest = base64::decode(header("content-md5")?).ok()?;
+				Some(Self::Md5(digest.as_slice().try_into().ok()?))
+			})
+	}
+}
+impl fmt::Display for Checksum {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Headers => f.write_str("checksum from headers"),
+			Self::S3Etag => f.write_str("checksum from S3 ETag"),
+			Self::Md5(digest) => write!(f, "md5:{}", hex::encode(digest)),
+			Self::Sha256(digest) => write!(f, "sha-256:{}", hex::encode(digest)),
+		}
+	}
+}
+
+/// Computes the checksum of a body as it's delivered, to compare against the expected one once it's complete.
+pub(crate) struct Verifier {
+	expected: Checksum,
+	hasher: Hasher,
+}
+enum Hasher {
+	Md5(Md5),
+	Sha256(Sha256),
+}
+impl Verifier {
+	/// A verifier of `checksum`, if the headers of the initial response give one where needed.
+	pub(crate) fn new(checksum: Checksum, headers: &reqwest::header::HeaderMap) -> Option<Self> {
+		let expected = checksum.resolve(headers)?;
+		let hasher = match expected {
+			Checksum::Headers | Checksum::S3Etag => unreachable!(),
+			Checksum::Md5(_) => Hasher::Md5(Md5::new()),
+			Checksum::Sha256(_) => Hasher::Sha256(Sha256::new()),
+		};
+		Some(Self { expected, hasher })
+	}
+	pub(crate) fn update(&mut self, chunk: &[u8]) {
+		match &mut self.hasher {
+			Hasher::Md5(hasher) => hasher.update(chunk),
+			Hasher::Sha256(hasher) => hasher.update(chunk),
+		}
+	}
+	pub(crate) fn verify(self) -> Result<()> {
+		let actual = match self.hasher {
+			Hasher::Md5(hasher) => Checksum::Md5(hasher.finalize().into()),
+			Hasher::Sha256(hasher) => Checksum::Sha256(hasher.finalize().into()),
+		};
+		if actual != self.expected {
+			return Err(Error::ChecksumMismatch {
+				expected: self.expected,
+				actual,
+			});
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,380 @@
+use bytes::Bytes;
+use std::{
+	io::{self, Write}, mem
+};
+
+use super::{RequestBuilder, Response, Result};
+
+impl RequestBuilder {
+	/// Decompress the body if the server compresses it, with whichever of the `gzip`, `brotli` and `zstd` features are enabled.
+	///
+	/// By default `Accept-Encoding: identity` is sent, so the body isn't compressed, as resuming relies on offsets into the body as sent. If this is enabled, the enabled compressions are accepted instead, and the body is decompressed after it's been resumed, so resuming is still of the compressed bytes. Offsets – of [`BodyReader::position()`](super::BodyReader::position), [`Stats`](super::Stats) and [`Event`](super::Event)s – and [`Response::content_length()`] remain those of the compressed body.
+	///
+	/// The automatic decompression of `reqwest`'s own `gzip` and `brotli` features counts offsets into the decompressed body, so must be left disabled on the underlying `reqwest::Client`.
+	pub fn decompress(mut self, decompress: bool) -> Self {
+		self.decompress = decompress;
+		self
+	}
+}
+impl Response {
+	/// Decompress the body with `codec`, for example if it's a `.gz` file.
+	///
+	/// The body is decompressed after it's been resumed, so resuming is still of the compressed bytes. If the body is also decompressed per its `Content-Encoding`, with [`RequestBuilder::decompress()`], this is applied after that.
+	pub fn decompressed(mut self, codec: Codec) -> Self {
+		self.decoder
+			.decompressor
+			.get_or_insert_with(Decompressor::default)
+			.push(codec.decoder());
+		self
+	}
+	/// Decompress the body with the [`Codec`] detected from its first bytes, or otherwise from the extension of the URL, leaving it as-is if neither is recognised.
+	///
+	/// See [`Response::decompressed()`].
+	pub fn decompressed_auto(mut self) -> Self {
+		let fallback = Codec::from_extension(self.url().path());
+		self.decoder
+			.decompressor
+			.get_or_insert_with(Decompressor::default)
+			.push(Box::new(Detect {
+				fallback,
+				magic: Vec::new(),
+				decoder: None,
+			}));
+		self
+	}
+}
+
+/// A compression format that a body can be decompressed from, each enabled by the cargo feature of the same name.
+///
+/// See [`Response::decompressed()`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Codec {
+	/// gzip, including multiple concatenated members, as written by some tools for large files.
+	#[cfg(feature = "gzip")]
+	Gzip,
+	/// Brotli.
+	#[cfg(feature = "brotli")]
+	Brotli,
+	/// Zstandard.
+	#[cfg(feature = "zstd")]
+	Zstd,
+	/// bzip2.
+	#[cfg(feature = "bzip2")]
+	Bzip2,
+	/// xz.
+	#[cfg(feature = "xz")]
+	Xz,
+}
+impl Codec {
+	/// The codec of data starting with `magic`, per its magic number. Brotli doesn't have one, so is never detected.
+	pub fn from_magic(magic: &[u8]) -> Option<Self> {
+		let codecs: &[(&[u8], Self)] = &[
+			#[cfg(feature = "gzip")]
+			(b"\x1f\x8b", Self::Gzip),
+			#[cfg(feature = "zstd")]
+			(b"\x28\xb5\x2f\xfd", Self::Zstd),
+			#[cfg(feature = "bzip2")]
+			(b"BZh", Self::Bzip2),
+			#[cfg(feature = "xz")]
+			(b"\xfd7zXZ\x00", Self::Xz),
+		];
+		codecs
+			.iter()
+			.find(|(prefix, _)| magic.starts_with(prefix))
+			.map(|&(_, codec)| codec)
+	}
+	/// The codec of a file at `path`, per its extension, for example `.gz`.
+	pub fn from_extension(path: &str) -> Option<Self> {
+		let (_, extension) = path.rsplit_once('.')?;
+		let codecs: &[(&str, Self)] = &[
+			#[cfg(feature = "gzip")]
+			("gz", Self::Gzip),
+			#[cfg(feature = "brotli")]
+			("br", Self::Brotli),
+			#[cfg(feature = "zstd")]
+			("zst", Self::Zstd),
+			#[cfg(feature = "bzip2")]
+			("bz2", Self::Bzip2),
+			#[cfg(feature = "xz")]
+			("xz", Self::Xz),
+		];
+		codecs
+			.iter()
+			.find(|(name, _)| extension.eq_ignore_ascii_case(name))
+			.map(|&(_, codec)| codec)
+	}
+	/// The codec of a `Content-Encoding`.
+	fn from_content_encoding(encoding: &str) -> Option<Self> {
+		let codecs: &[(&str, Self)] = &[
+			#[cfg(feature = "gzip")]
+			("gzip", Self::Gzip),
+			#[cfg(feature = "gzip")]
+			("x-gzip", Self::Gzip),
+			#[cfg(feature = "brotli")]
+			("br", Self::Brotli),
+			#[cfg(feature = "zstd")]
+			("zstd", Self::Zstd),
+		];
+		codecs
+			.iter()
+			.find(|(name, _)| encoding.eq_ignore_ascii_case(name))
+			.map(|&(_, codec)| codec)
+	}
+	fn decoder(self) -> Box<dyn Decode> {
+		match self {
+			#[cfg(feature = "gzip")]
+			Self::Gzip => Box::new(flate2::write::MultiGzDecoder::new(Vec::new())),
+			#[cfg(feature = "brotli")]
+			Self::Brotli => Box::new(brotli_decompressor::DecompressorWriter::new(
+				Vec::new(),
+				4096,
+			)),
+			#[cfg(feature = "zstd")]
+			Self::Zstd => Box::new(zstd::stream::zio::Writer::new(
+				Vec::new(),
+				// This only fails if allocating its context does
+				zstd::stream::raw::Decoder::new().expect("failed to create zstd decoder"),
+			)),
+			#[cfg(feature = "bzip2")]
+			Self::Bzip2 => Box::new(Bzip2Decoder::new()),
+			#[cfg(feature = "xz")]
+			Self::Xz => Box::new(xz2::write::XzDecoder::new_multi_decoder(Vec::new())),
+		}
+	}
+}
+
+/// The value of `Accept-Encoding` to send.
+pub(crate) fn accept_encoding(decompress: bool) -> reqwest::header::HeaderValue {
+	let encodings: &[&str] = &[
+		#[cfg(feature = "gzip")]
+		"gzip",
+		#[cfg(feature = "brotli")]
+		"br",
+		#[cfg(feature = "zstd")]
+		"zstd",
+	];
+	if !decompress || encodings.is_empty() {
+		return reqwest::header::HeaderValue::from_static("identity");
+	}
+	encodings.join(", ").parse().unwrap()
+}
+
+/// Decompresses a body chunk by chunk as it's delivered, with each of a sequence of decoders in turn.
+#[derive(Default)]
+pub(crate) struct Decompressor(Vec<Box<dyn Decode>>);
+impl Decompressor {
+	/// A decompressor of the body of a response with `headers` per its `Content-Encoding`, or `None` if it isn't compressed.
+	pub(crate) fn from_headers(headers: &reqwest::header::HeaderMap) -> Result<Option<Self>> {
+		let encoding = match headers.get(reqwest::header::CONTENT_ENCODING) {
+			Some(encoding) => encoding.to_str().unwrap_or_default().trim(),
+			None => return Ok(None),
+		};
+		if encoding.eq_ignore_ascii_case("identity") {
+			return Ok(None);
+		}
+		let codec = Codec::from_content_encoding(encoding).ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("unsupported Content-Encoding: {encoding}"),
+			)
+		})?;
+		Ok(Some(Self(vec![codec.decoder()])))
+	}
+	fn push(&mut self, decoder: Box<dyn Decode>) {
+		self.0.push(decoder);
+	}
+	pub(crate) fn decompress(&mut self, chunk: &[u8]) -> io::Result<Bytes> {
+		let mut chunk = chunk.to_vec();
+		for decoder in &mut self.0 {
+			decoder.write_all(&chunk)?;
+			decoder.flush()?;
+			chunk = decoder.take_output();
+		}
+		Ok(chunk.into())
+	}
+	/// Decompress the end of the body, failing if it was incomplete.
+	pub(crate) fn finish(mut self) -> io::Result<Bytes> {
+		let mut chunk = Vec::new();
+		for decoder in &mut self.0 {
+			decoder.write_all(&chunk)?;
+			chunk = decoder.finish()?;
+		}
+		Ok(chunk.into())
+	}
+}
+
+/// A decoder that's written compressed bytes, and accumulates the decompressed bytes.
+trait Decode: Write + Send {
+	fn take_output(&mut self) -> Vec<u8>;
+	/// Decode the end of the input, returning the rest of the output.
+	fn finish(&mut self) -> io::Result<Vec<u8>>;
+}
+/// Passes bytes through as they are.
+impl Decode for Vec<u8> {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(self)
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		Ok(mem::take(self))
+	}
+}
+#[cfg(feature = "gzip")]
+impl Decode for flate2::write::MultiGzDecoder<Vec<u8>> {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(self.get_mut())
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		self.try_finish()?;
+		Ok(self.take_output())
+	}
+}
+#[cfg(feature = "brotli")]
+impl Decode for brotli_decompressor::DecompressorWriter<Vec<u8>> {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(self.get_mut())
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		self.close()?;
+		Ok(self.take_output())
+	}
+}
+#[cfg(feature = "zstd")]
+impl Decode for zstd::stream::zio::Writer<Vec<u8>, zstd::stream::raw::Decoder<'static>> {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(self.writer_mut())
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		// Unlike flushing, this fails if the frame is incomplete
+		zstd::stream::zio::Writer::finish(self)?;
+		Ok(self.take_output())
+	}
+}
+/// A bzip2 decoder of concatenated streams, as written by pbzip2. Unlike [`bzip2::write::BzDecoder`], finishing an incomplete stream fails rather than never returning.
+#[cfg(feature = "bzip2")]
+struct Bzip2Decoder {
+	decompress: bzip2::Decompress,
+	output: Vec<u8>,
+	done: bool,
+}
+#[cfg(feature = "bzip2")]
+impl Bzip2Decoder {
+	fn new() -> Self {
+		Self {
+			decompress: bzip2::Decompress::new(false),
+			output: Vec::new(),
+			done: false,
+		}
+	}
+	/// Decompress from `input` into the output, returning the number of bytes of it consumed.
+	fn decompress(&mut self, input: &[u8]) -> io::Result<usize> {
+		use std::convert::TryFrom;
+		self.output.reserve(32 * 1024);
+		let before = self.decompress.total_in();
+		let status = self
+			.decompress
+			.decompress_vec(input, &mut self.output)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+		self.done = status == bzip2::Status::StreamEnd;
+		Ok(usize::try_from(self.decompress.total_in() - before).unwrap())
+	}
+}
+#[cfg(feature = "bzip2")]
+impl Write for Bzip2Decoder {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let mut read = 0;
+		while read < buf.len() {
+			if self.done {
+				self.decompress = bzip2::Decompress::new(false);
+			}
+			read += self.decompress(&buf[read..])?;
+		}
+		Ok(read)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+#[cfg(feature = "bzip2")]
+impl Decode for Bzip2Decoder {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(&mut self.output)
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		while !self.done {
+			let before = self.decompress.total_out();
+			let _ = self.decompress(&[])?;
+			if !self.done && self.decompress.total_out() == before {
+				return Err(io::Error::new(
+					io::ErrorKind::UnexpectedEof,
+					"bzip2 stream was incomplete",
+				));
+			}
+		}
+		Ok(self.take_output())
+	}
+}
+#[cfg(feature = "xz")]
+impl Decode for xz2::write::XzDecoder<Vec<u8>> {
+	fn take_output(&mut self) -> Vec<u8> {
+		mem::take(self.get_mut())
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		xz2::write::XzDecoder::finish(self)
+	}
+}
+
+/// Buffers the first bytes of the body until its [`Codec`] can be detected from them, and then decodes with it.
+struct Detect {
+	fallback: Option<Codec>,
+	magic: Vec<u8>,
+	decoder: Option<Box<dyn Decode>>,
+}
+impl Detect {
+	/// The length of the longest magic number.
+	const MAGIC_LEN: usize = 6;
+
+	fn decoder(&mut self) -> io::Result<&mut Box<dyn Decode>> {
+		if self.decoder.is_none() {
+			let mut decoder: Box<dyn Decode> =
+				match Codec::from_magic(&self.magic).or(self.fallback) {
+					Some(codec) => codec.decoder(),
+					None => Box::new(Vec::new()),
+				};
+			decoder.write_all(&mem::take(&mut self.magic))?;
+			self.decoder = Some(decoder);
+		}
+		Ok(self.decoder.as_mut().unwrap())
+	}
+}
+impl Write for Detect {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.decoder.is_none() && self.magic.len() + buf.len() < Self::MAGIC_LEN {
+			self.magic.extend_from_slice(buf);
+			return Ok(buf.len());
+		}
+		if self.decoder.is_none() {
+			self.magic.extend_from_slice(buf);
+			let _ = self.decoder()?;
+			return Ok(buf.len());
+		}
+		self.decoder()?.write(buf)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		match &mut self.decoder {
+			Some(decoder) => decoder.flush(),
+			None => Ok(()),
+		}
+	}
+}
+impl Decode for Detect {
+	fn take_output(&mut self) -> Vec<u8> {
+		self.decoder
+			.as_mut()
+			.map(|decoder| decoder.take_output())
+			.unwrap_or_default()
+	}
+	fn finish(&mut self) -> io::Result<Vec<u8>> {
+		self.decoder()?.finish()
+	}
+}
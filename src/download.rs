@@ -0,0 +1,180 @@
+use futures::TryStreamExt;
+use std::{
+	ffi::OsString, fmt, future::Future, io, path::{Path, PathBuf}, str::FromStr
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use super::{Client, Error, Result, Validator};
+
+/// How many bytes are written between updates of the metadata file.
+const SAVE_INTERVAL: u64 = 1024 * 1024;
+
+impl Client {
+	/// Download a URL to a file, resuming a previous partial download of it if there is one.
+	///
+	/// The body is written to `<path>.part`, alongside a small `<path>.part.meta` file recording the URL, `ETag`, `Last-Modified`, total length and number of bytes written. If the process is killed, calling this again with the same `url` and `path` resumes with a `Range` request from where it left off, provided the resource hasn't changed on the server. Once complete, `<path>.part` is renamed to `path` and the metadata file is removed.
+	///
+	/// Fails without writing to `path` if the response has an unsuccessful status.
+	///
+	/// Resolves to the length of the downloaded file.
+	pub fn download_to_file<U, P>(
+		&self, url: U, path: P,
+	) -> impl Future<Output = Result<u64>> + Send
+	where
+		U: reqwest::IntoUrl,
+		P: AsRef<Path>,
+	{
+		let (client, url, path) = (self.clone(), url.into_url(), path.as_ref().to_owned());
+		async move {
+			let url = url?;
+			let part = with_suffix(&path, ".part");
+			let meta_path = with_suffix(&path, ".part.meta");
+			let resume = match Metadata::load(&meta_path).await? {
+				Some(meta) if meta.url == url => {
+					let len = match fs::metadata(&part).await {
+						Ok(metadata) => metadata.len(),
+						Err(err) if err.kind() == io::ErrorKind::NotFound => 0,
+						Err(err) => return Err(err.into()),
+					};
+					Some((meta.written.min(len), meta.validator)).filter(|&(pos, _)| pos > 0)
+				}
+				_ => None,
+			};
+			let (mut response, resumed) = match resume {
+				Some((pos, validator)) => {
+					match client
+						.get(url.clone())
+						.send_range(pos, None, validator)
+						.await
+					{
+						// The resource changed, or is now shorter than what was written, so is downloaded afresh
+						Err(err)
+							if err.is_resource_changed()
+								|| err.status()
+									== Some(reqwest::StatusCode::RANGE_NOT_SATISFIABLE) =>
+						{
+							fs::remove_file(&meta_path).await?;
+							(client.get(url.clone()).send().await?, false)
+						}
+						response => (response?, true),
+					}
+				}
+				None => (client.get(url.clone()).send().await?, false),
+			};
+			// A range from the end of the body can't be satisfied, so a previous run wrote all of it, but was stopped before moving it into place
+			let complete =
+				resumed && response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE;
+			if !complete {
+				response = response.success()?;
+			}
+			let pos = response.decoder.pos;
+			let length = response.total_length();
+			let mut meta = Metadata {
+				url,
+				validator: response.decoder.validator.clone(),
+				length,
+				written: pos,
+			};
+			meta.save(&meta_path).await?;
+			let mut file = fs::OpenOptions::new()
+				.append(true)
+				.create(true)
+				.open(&part)
+				.await?;
+			file.set_len(pos).await?;
+			let mut body = response.bytes_stream();
+			let mut saved = pos;
+			while let Some(chunk) = body.try_next().await? {
+				file.write_all(&chunk).await?;
+				meta.written += chunk.len() as u64;
+				if meta.written - saved >= SAVE_INTERVAL {
+					meta.save(&meta_path).await?;
+					saved = meta.written;
+				}
+			}
+			file.sync_all().await?;
+			meta.save(&meta_path).await?;
+			if let Some(length) = length.filter(|&length| meta.written != length) {
+				return Err(Error::Truncated {
+					expected: length,
+					received: meta.written,
+				});
+			}
+			fs::rename(&part, &path).await?;
+			fs::remove_file(&meta_path).await?;
+			Ok(meta.written)
+		}
+	}
+}
+
+/// The sidecar metadata for a partial download, persisted as `key value` lines.
+struct Metadata {
+	url: reqwest::Url,
+	validator: Validator,
+	length: Option<u64>,
+	written: u64,
+}
+impl Metadata {
+	/// Load the metadata if the file exists and is valid.
+	async fn load(path: &Path) -> Result<Option<Self>> {
+		match fs::read_to_string(path).await {
+			Ok(meta) => Ok(Self::parse(&meta)),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err.into()),
+		}
+	}
+	/// Save the metadata, replacing the file atomically so a crash doesn't leave it torn.
+	async fn save(&self, path: &Path) -> Result<()> {
+		let tmp = with_suffix(path, ".tmp");
+		fs::write(&tmp, self.to_string()).await?;
+		fs::rename(&tmp, path).await?;
+		Ok(())
+	}
+	fn parse(meta: &str) -> Option<Self> {
+		let (mut url, mut etag, mut last_modified, mut length, mut written) =
+			(None, None, None, None, None);
+		for line in meta.lines() {
+			let (key, value) = line.split_once(' ')?;
+			match key {
+				"url" => url = Some(value.parse().ok()?),
+				"etag" => etag = Some(hyperx::header::EntityTag::from_str(value).ok()?),
+				"last-modified" => last_modified = Some(value.parse().ok()?),
+				"length" => length = Some(value.parse().ok()?),
+				"written" => written = Some(value.parse().ok()?),
+				_ => return None,
+			}
+		}
+		Some(Self {
+			url: url?,
+			validator: Validator {
+				etag,
+				last_modified,
+			},
+			length,
+			written: written?,
+		})
+	}
+}
+
+impl fmt::Display for Metadata {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "url {}", self.url)?;
+		if let Some(etag) = &self.validator.etag {
+			writeln!(f, "etag {etag}")?;
+		}
+		if let Some(last_modified) = &self.validator.last_modified {
+			writeln!(f, "last-modified {last_modified}")?;
+		}
+		if let Some(length) = self.length {
+			writeln!(f, "length {length}")?;
+		}
+		writeln!(f, "written {}", self.written)
+	}
+}
+
+/// `path` with `suffix` appended to its file name.
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+	let mut path = OsString::from(path);
+	path.push(suffix);
+	path.into()
+}
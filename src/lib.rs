@@ -10,19 +10,14 @@
 //!
 //! ```
 //! use async_compression::futures::bufread::GzipDecoder;
-//! use futures::{io::BufReader, AsyncBufReadExt, StreamExt, TryStreamExt};
-//! use std::io;
+//! use futures::{io::BufReader, AsyncBufReadExt, StreamExt};
 //!
 //! # #[tokio::main]
 //! # async fn main() {
 //! let url = "http://commoncrawl.s3.amazonaws.com/crawl-data/CC-MAIN-2018-30/warc.paths.gz";
-//! let body = reqwest_resume::get(url.parse().unwrap()).await.unwrap();
+//! let body = reqwest_resume::get(url).await.unwrap();
 //! // Content-Encoding isn't set, so decode manually
-//! let body = body
-//!     .bytes_stream()
-//!     .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
-//! let body = BufReader::new(body.into_async_read());
-//! let mut body = GzipDecoder::new(body); // Content-Encoding isn't set, so decode manually
+//! let mut body = GzipDecoder::new(body.into_async_read());
 //! body.multiple_members(true);
 //!
 //! let mut lines = BufReader::new(body).lines();
@@ -47,16 +42,48 @@
 #![allow(
 	clippy::new_without_default,
 	clippy::must_use_candidate,
-	clippy::missing_errors_doc
+	clippy::missing_errors_doc,
+	clippy::struct_field_names,
+	clippy::return_self_not_must_use,
+	clippy::duration_suboptimal_units
 )]
 
-use bytes::Bytes;
-use futures::{ready, FutureExt, Stream, TryFutureExt};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{
+	future::{self, BoxFuture}, ready, stream::BoxStream, FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt
+};
 use std::{
-	future::Future, pin::Pin, task::{Context, Poll}, time::Duration
+	convert::TryFrom, error, fmt, future::Future, io, net::SocketAddr, ops::{Bound, RangeBounds}, pin::Pin, sync::Arc, task::{Context, Poll}, time::{Duration, Instant, SystemTime}
 };
 use tokio::time::delay_for as sleep;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod checksum;
+mod decompress;
+mod download;
+mod progress;
+mod reader;
+mod records;
+mod remote_file;
+mod segmented;
+mod stats;
+mod trace;
+
+use checksum::Verifier;
+use decompress::Decompressor;
+use progress::Observer;
+use stats::Tracker;
+use trace::Trace;
+
+pub use checksum::Checksum;
+pub use decompress::Codec;
+pub use progress::Event;
+pub use reader::BodyReader;
+pub use remote_file::RemoteFile;
+pub use segmented::Segmented;
+pub use stats::Stats;
+
 /// Extension to [`reqwest::Client`] that provides a method to convert it
 pub trait ClientExt {
 	/// Convert a [`reqwest::Client`] into a [`reqwest_resume::Client`](Client)
@@ -64,28 +91,113 @@ pub trait ClientExt {
 }
 impl ClientExt for reqwest::Client {
 	fn resumable(self) -> Client {
-		Client(self)
+		Client {
+			client: self,
+			retry_policy: RetryPolicy::new(),
+			observer: Observer::default(),
+		}
 	}
 }
 
 /// A `Client` to make Requests with.
 ///
 /// See [`reqwest::Client`].
-#[derive(Debug)]
-pub struct Client(reqwest::Client);
+#[derive(Clone, Debug)]
+pub struct Client {
+	client: reqwest::Client,
+	retry_policy: RetryPolicy,
+	observer: Observer,
+}
 impl Client {
 	/// Constructs a new `Client`.
 	///
 	/// See [`reqwest::Client::new()`].
 	pub fn new() -> Self {
-		Self(reqwest::Client::new())
+		reqwest::Client::new().resumable()
+	}
+	/// Set the [`RetryPolicy`] used by requests made with this `Client`.
+	pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.retry_policy = retry_policy;
+		self
 	}
 	/// Convenience method to make a `GET` request to a URL.
 	///
 	/// See [`reqwest::Client::get()`].
-	pub fn get(&self, url: reqwest::Url) -> RequestBuilder {
-		// <U: reqwest::IntoUrl>
-		RequestBuilder(self.0.clone(), reqwest::Method::GET, url)
+	pub fn get<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::GET, url)
+	}
+	/// Convenience method to make a `HEAD` request to a URL.
+	///
+	/// See [`reqwest::Client::head()`].
+	pub fn head<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::HEAD, url)
+	}
+	/// Convenience method to make a `POST` request to a URL.
+	///
+	/// `POST` isn't idempotent, so it's only retried or resumed if opted in to with [`RequestBuilder::idempotency_key()`] or [`RequestBuilder::idempotent()`].
+	///
+	/// See [`reqwest::Client::post()`].
+	pub fn post<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::POST, url)
+	}
+	/// Convenience method to make a `PUT` request to a URL.
+	///
+	/// See [`reqwest::Client::put()`].
+	pub fn put<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::PUT, url)
+	}
+	/// Convenience method to make a `PATCH` request to a URL.
+	///
+	/// `PATCH` isn't idempotent, so it's only retried or resumed if opted in to with [`RequestBuilder::idempotency_key()`] or [`RequestBuilder::idempotent()`].
+	///
+	/// See [`reqwest::Client::patch()`].
+	pub fn patch<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::PATCH, url)
+	}
+	/// Convenience method to make a `DELETE` request to a URL.
+	///
+	/// See [`reqwest::Client::delete()`].
+	pub fn delete<U>(&self, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		self.request(reqwest::Method::DELETE, url)
+	}
+	/// Start building a `Request` with the `Method` and `Url`.
+	///
+	/// Requests with an idempotent method (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE`) are retried and resumed per the [`RetryPolicy`]. Others are sent once and their body isn't resumed, unless opted in to with [`RequestBuilder::idempotency_key()`] or [`RequestBuilder::idempotent()`].
+	///
+	/// See [`reqwest::Client::request()`].
+	pub fn request<U>(&self, method: reqwest::Method, url: U) -> RequestBuilder
+	where
+		U: reqwest::IntoUrl,
+	{
+		RequestBuilder {
+			client: self.client.clone(),
+			request: built(self.client.request(method, url)),
+			retry_policy: self.retry_policy.clone(),
+			observer: self.observer.clone(),
+			idempotent: None,
+			range: None,
+			if_range: None,
+			checksum: None,
+			decompress: false,
+		}
 	}
 }
 
@@ -93,39 +205,296 @@ impl Client {
 ///
 /// See [`reqwest::RequestBuilder`].
 #[derive(Debug)]
-pub struct RequestBuilder(reqwest::Client, reqwest::Method, reqwest::Url);
+pub struct RequestBuilder {
+	client: reqwest::Client,
+	request: std::result::Result<reqwest::RequestBuilder, Arc<reqwest::Error>>,
+	retry_policy: RetryPolicy,
+	observer: Observer,
+	idempotent: Option<bool>,
+	range: Option<ByteRange>,
+	if_range: Option<Validator>,
+	checksum: Option<Checksum>,
+	decompress: bool,
+}
 impl RequestBuilder {
+	/// Set the [`RetryPolicy`] used by this request, overriding that of the [`Client`].
+	pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+		self.retry_policy = retry_policy;
+		self
+	}
+	/// Add a header to the request.
+	///
+	/// Headers are sent with every retry and resume, alongside the `Range` and `If-Range` headers used to resume.
+	///
+	/// See [`reqwest::RequestBuilder::header()`].
+	pub fn header<K, V>(self, key: K, value: V) -> Self
+	where
+		reqwest::header::HeaderName: TryFrom<K>,
+		<reqwest::header::HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+		reqwest::header::HeaderValue: TryFrom<V>,
+		<reqwest::header::HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+	{
+		self.map(|request| request.header(key, value))
+	}
+	/// Add a set of headers to the request, replacing any already set with the same names.
+	///
+	/// See [`reqwest::RequestBuilder::headers()`].
+	pub fn headers(self, headers: reqwest::header::HeaderMap) -> Self {
+		self.map(|request| request.headers(headers))
+	}
+	/// Enable HTTP basic authentication.
+	///
+	/// See [`reqwest::RequestBuilder::basic_auth()`].
+	pub fn basic_auth<U, P>(self, username: U, password: Option<P>) -> Self
+	where
+		U: fmt::Display,
+		P: fmt::Display,
+	{
+		self.map(|request| request.basic_auth(username, password))
+	}
+	/// Enable HTTP bearer authentication.
+	///
+	/// See [`reqwest::RequestBuilder::bearer_auth()`].
+	pub fn bearer_auth<T>(self, token: T) -> Self
+	where
+		T: fmt::Display,
+	{
+		self.map(|request| request.bearer_auth(token))
+	}
+	/// Modify the query string of the URL.
+	///
+	/// See [`reqwest::RequestBuilder::query()`].
+	pub fn query<T>(self, query: &T) -> Self
+	where
+		T: serde::Serialize + ?Sized,
+	{
+		self.map(|request| request.query(query))
+	}
+	/// Set a timeout for each attempt, from when it starts connecting until its body has finished.
+	///
+	/// An attempt that times out while receiving the body is resumed like any other failure; so this bounds how long a connection can stall, rather than the whole download.
+	///
+	/// See [`reqwest::RequestBuilder::timeout()`].
+	pub fn timeout(self, timeout: Duration) -> Self {
+		self.map(|request| request.timeout(timeout))
+	}
+	/// Set the request body.
+	///
+	/// The body is buffered so that it can be sent again on each retry and resume.
+	///
+	/// See [`reqwest::RequestBuilder::body()`].
+	pub fn body<T>(self, body: T) -> Self
+	where
+		T: Into<Bytes>,
+	{
+		self.map(|request| request.body(body.into()))
+	}
+	/// Apply `f` to the underlying request.
+	fn map<F>(mut self, f: F) -> Self
+	where
+		F: FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+	{
+		self.request = self.request.and_then(|request| built(f(request)));
+		self
+	}
+	/// Set whether the request may be retried and resumed, overriding the default of doing so only for idempotent methods.
+	///
+	/// Only enable this for a non-idempotent method like `POST` if the server handles repeats of the request safely.
+	pub fn idempotent(mut self, idempotent: bool) -> Self {
+		self.idempotent = Some(idempotent);
+		self
+	}
+	/// Send an `Idempotency-Key` header with `key`, so the server can recognise repeats of the request, and allow it to be retried and resumed.
+	pub fn idempotency_key(self, key: &str) -> Self {
+		self.map(|request| request.header("idempotency-key", key))
+			.idempotent(true)
+	}
+	/// Request only a range of the body, for example `start..end` or `start..`.
+	///
+	/// The [`Response`] body is just the bytes in the range, and is resumed from wherever it got to and stopped at the end of the range. If the server ignores the `Range` header and responds with the whole body, the bytes outside the range are discarded.
+	///
+	/// # Panics
+	///
+	/// Panics if the range is empty.
+	pub fn range<R>(self, range: R) -> Self
+	where
+		R: RangeBounds<u64>,
+	{
+		let start = match range.start_bound() {
+			Bound::Included(&start) => start,
+			Bound::Excluded(&start) => start.checked_add(1).expect("range must be non-empty"),
+			Bound::Unbounded => 0,
+		};
+		let end = match range.end_bound() {
+			// An inclusive end of `u64::MAX` is past the end of any body
+			Bound::Included(&end) => end.checked_add(1),
+			Bound::Excluded(&end) => Some(end),
+			Bound::Unbounded => None,
+		};
+		assert!(end.is_none_or(|end| start < end), "range must be non-empty");
+		self.byte_range(start, end)
+	}
+	/// Request only the last `n` bytes of the body.
+	///
+	/// The whole body is returned if it's shorter than `n` bytes. If the server ignores the `Range` header, its `Content-Length` is used to discard all but the last `n` bytes.
+	pub fn suffix(mut self, n: u64) -> Self {
+		self.range = Some(ByteRange::Last(n));
+		self
+	}
+	/// Request only the bytes from `start` up to but excluding `end`, or to the end of the body if `None`.
+	fn byte_range(mut self, start: u64, end: Option<u64>) -> Self {
+		self.range = Some(ByteRange::FromTo(start, end));
+		self
+	}
+	/// Request the range only if the resource is still the representation identified by `validator`. If it isn't, the returned [`Response`] is of the whole new representation, starting at 0.
+	fn if_range(mut self, validator: Validator) -> Self {
+		self.if_range = Some(validator);
+		self
+	}
+	/// Send a request for the bytes from `start` up to but excluding `end`, or to the end of the body if `None`, of the representation identified by `validator`, failing with [`Error::ResourceChanged`] if the response is of a different one.
+	///
+	/// A weak validator can't be sent with `If-Range`, and a server might not notice the change anyway, so the validators of the response are compared too.
+	async fn send_range(
+		self, start: u64, end: Option<u64>, validator: Validator,
+	) -> Result<Response> {
+		let response = self
+			.byte_range(start, end)
+			.if_range(validator.clone())
+			.send()
+			.await?;
+		if response.decoder.pos != start || !validator.matches(&response.decoder.validator) {
+			return Err(Error::ResourceChanged);
+		}
+		Ok(response)
+	}
 	/// Constructs the Request and sends it the target URL, returning a Response.
 	///
 	/// See [`reqwest::RequestBuilder::send()`].
-	pub fn send(&mut self) -> impl Future<Output = reqwest::Result<Response>> + Send {
-		let (client, method, url) = (self.0.clone(), self.1.clone(), self.2.clone());
+	#[allow(clippy::too_many_lines)]
+	pub fn send(&mut self) -> impl Future<Output = Result<Response>> + Send {
+		let request = self
+			.request
+			.as_ref()
+			.map(RequestExt::clone_buffered)
+			.map_err(|err| Error::Builder(err.clone()));
+		let (client, retry_policy, observer) = (
+			self.client.clone(),
+			self.retry_policy.clone(),
+			self.observer.clone(),
+		);
+		let (idempotent, range, if_range, checksum, decompress) = (
+			self.idempotent,
+			self.range,
+			self.if_range.clone(),
+			self.checksum.clone(),
+			self.decompress,
+		);
 		async move {
+			let mut request = request?.build()?;
+			// Offsets are into the body as sent, so it's only compressed if it's to be decompressed after resuming
+			let _ = request
+				.headers_mut()
+				.entry(reqwest::header::ACCEPT_ENCODING)
+				.or_insert_with(|| decompress::accept_encoding(decompress));
+			let mut trace = Trace::new(&request);
+			let mut tracker = Tracker::new();
+			let idempotent = idempotent.unwrap_or_else(|| request.method().is_idempotent());
+			// Requests that can't safely be repeated are attempted just once
+			let mut retry = Retry::new(if idempotent {
+				retry_policy
+			} else {
+				retry_policy.max_attempts(1)
+			});
 			let response = loop {
-				let builder = client.request(method.clone(), url.clone());
-				match builder.send().await {
+				let mut attempt = request.clone_buffered();
+				if let Some(range) = range {
+					attempt
+						.headers_mut()
+						.extend(range_headers(range, if_range.as_ref()));
+				}
+				trace.attempt(match range {
+					Some(ByteRange::FromTo(start, _)) => start,
+					_ => 0,
+				});
+				tracker.stats.attempts += 1;
+				let (err, retry_after) = match trace.instrument(client.execute(attempt)).await {
+					Ok(response) => match retry.policy.retryable_status(&response) {
+						Some(err) => (err, retry_after(response.headers())),
+						None => break response,
+					},
 					Err(err) if !err.is_builder() && !err.is_redirect() && !err.is_status() => {
-						sleep(Duration::from_secs(1)).await
+						(err, None)
+					}
+					Err(err) => return Err(err.into()),
+				};
+				let err = err.into();
+				let event = Event::Error { error: &err };
+				observer.emit(&event);
+				trace.event(&event);
+				match retry.next_delay(retry_after) {
+					Some(delay) => {
+						let event = Event::RetryScheduled { delay };
+						observer.emit(&event);
+						trace.event(&event);
+						tracker.stats.backoff += delay;
+						sleep(delay).await;
+					}
+					None => {
+						return Err(Error::RetriesExhausted {
+							attempts: retry.failures,
+							source: Box::new(err),
+						})
 					}
-					x => break x?,
 				}
 			};
 			let headers = hyperx::Headers::from(response.headers());
-			let accept_byte_ranges =
-				if let Some(&hyperx::header::AcceptRanges(ref ranges)) = headers.get() {
-					ranges
-						.iter()
-						.any(|u| *u == hyperx::header::RangeUnit::Bytes)
-				} else {
-					false
-				};
+			let accept_byte_ranges = response.status() == reqwest::StatusCode::PARTIAL_CONTENT
+				|| accept_byte_ranges(&headers);
+			let validator = Validator::from_headers(&headers);
+			let (pos, end, skip) = position(range, if_range.as_ref(), &response, &validator)?;
+			let total = total_length(&response);
+			let body_end = body_end(&response);
+			let decompressor = if decompress {
+				Decompressor::from_headers(response.headers())?
+			} else {
+				None
+			};
+			let verifier = checksum
+				.filter(|_| range.is_none())
+				.and_then(|checksum| Verifier::new(checksum, response.headers()));
+			let event = Event::Connected {
+				status: response.status(),
+				pos,
+				total,
+			};
+			observer.emit(&event);
+			trace.event(&event);
 			Ok(Response {
-				client,
-				method,
-				url,
-				response,
-				accept_byte_ranges,
-				pos: 0,
+				status: response.status(),
+				status_error: response.error_for_status_ref().err(),
+				headers: response.headers().clone(),
+				url: response.url().clone(),
+				remote_addr: response.remote_addr(),
+				content_length: response.content_length(),
+				decoder: Decoder {
+					client,
+					request,
+					state: State::Body(Box::pin(response.bytes_stream())),
+					accept_byte_ranges,
+					validator,
+					retry: Retry::new(retry.policy),
+					pos,
+					end,
+					skip,
+					restarted: 0,
+					tracker,
+					total,
+					body_end,
+					verifier,
+					decompressor,
+					observer,
+					trace,
+				},
 			})
 		}
 	}
@@ -133,90 +502,2012 @@ impl RequestBuilder {
 
 /// A Response to a submitted Request.
 ///
+/// The status, headers, URL and so on are those of the initial response; the body continues across resumes.
+///
 /// See [`reqwest::Response`].
 #[derive(Debug)]
 pub struct Response {
-	client: reqwest::Client,
-	method: reqwest::Method,
+	status: reqwest::StatusCode,
+	status_error: Option<reqwest::Error>,
+	headers: reqwest::header::HeaderMap,
 	url: reqwest::Url,
-	response: reqwest::Response,
-	accept_byte_ranges: bool,
-	pos: u64,
+	remote_addr: Option<SocketAddr>,
+	content_length: Option<u64>,
+	decoder: Decoder,
 }
 impl Response {
+	/// Get the `StatusCode` of this `Response`.
+	///
+	/// See [`reqwest::Response::status()`].
+	pub fn status(&self) -> reqwest::StatusCode {
+		self.status
+	}
+	/// Get the `Headers` of this `Response`.
+	///
+	/// See [`reqwest::Response::headers()`].
+	pub fn headers(&self) -> &reqwest::header::HeaderMap {
+		&self.headers
+	}
+	/// Get the content-length of this response, if known.
+	///
+	/// See [`reqwest::Response::content_length()`].
+	pub fn content_length(&self) -> Option<u64> {
+		self.content_length
+	}
+	/// Get the final `Url` of this `Response`, after any redirects.
+	///
+	/// See [`reqwest::Response::url()`].
+	pub fn url(&self) -> &reqwest::Url {
+		&self.url
+	}
+	/// Get the remote address used to get this `Response`.
+	///
+	/// See [`reqwest::Response::remote_addr()`].
+	pub fn remote_addr(&self) -> Option<SocketAddr> {
+		self.remote_addr
+	}
+	/// Turn a response with a client or server error status into an error.
+	///
+	/// See [`reqwest::Response::error_for_status()`].
+	pub fn error_for_status(mut self) -> Result<Self> {
+		match self.status_error.take() {
+			Some(err) => Err(err.into()),
+			None => Ok(self),
+		}
+	}
+	/// Get the full response body as `Bytes`.
+	///
+	/// See [`reqwest::Response::bytes()`].
+	pub fn bytes(self) -> impl Future<Output = Result<Bytes>> + Send {
+		self.decoder
+			.try_fold(BytesMut::new(), |mut body, chunk| {
+				body.extend_from_slice(&chunk);
+				future::ok(body)
+			})
+			.map_ok(BytesMut::freeze)
+	}
+	/// Get the full response text, decoded per the `charset` of the `Content-Type` header, defaulting to UTF-8.
+	///
+	/// See [`reqwest::Response::text()`].
+	pub fn text(self) -> impl Future<Output = Result<String>> + Send {
+		let encoding = self.encoding();
+		self.bytes()
+			.map_ok(move |body| encoding.decode(&body).0.into_owned())
+	}
+	/// Try to deserialize the response body as JSON.
+	///
+	/// See [`reqwest::Response::json()`].
+	#[cfg(feature = "json")]
+	pub fn json<T>(self) -> impl Future<Output = Result<T>> + Send
+	where
+		T: serde::de::DeserializeOwned,
+	{
+		self.bytes().map(|body| Ok(serde_json::from_slice(&body?)?))
+	}
+	/// Stream a chunk of the response body, or `None` once it's complete.
+	///
+	/// See [`reqwest::Response::chunk()`].
+	pub async fn chunk(&mut self) -> Result<Option<Bytes>> {
+		self.decoder.next().await.transpose()
+	}
 	/// Convert the response into a `Stream` of `Bytes` from the body.
 	///
 	/// See [`reqwest::Response::bytes_stream()`].
-	pub fn bytes_stream(self) -> impl Stream<Item = reqwest::Result<Bytes>> + Send {
-		Decoder {
-			client: self.client,
-			method: self.method,
-			url: self.url,
-			decoder: Box::pin(self.response.bytes_stream()),
-			accept_byte_ranges: self.accept_byte_ranges,
-			pos: self.pos,
+	pub fn bytes_stream(self) -> impl Stream<Item = Result<Bytes>> + Send {
+		self.decoder
+	}
+	/// Fail unless the response has a successful status, so its body is of the resource requested.
+	fn success(self) -> Result<Self> {
+		let response = self.error_for_status()?;
+		if !response.status.is_success() {
+			return Err(Error::UnexpectedStatus(response.status));
+		}
+		Ok(response)
+	}
+	/// The encoding of the text of the body, per the `charset` of the `Content-Type` header, defaulting to UTF-8.
+	fn encoding(&self) -> &'static encoding_rs::Encoding {
+		self.headers
+			.get(reqwest::header::CONTENT_TYPE)
+			.and_then(|value| value.to_str().ok())
+			.and_then(|value| value.parse::<mime::Mime>().ok())
+			.and_then(|mime| {
+				mime.get_param(mime::CHARSET).and_then(|charset| {
+					encoding_rs::Encoding::for_label(charset.as_str().as_bytes())
+				})
+			})
+			.unwrap_or(encoding_rs::UTF_8)
+	}
+	/// The length of the whole representation, per the `Content-Range` header if this is a partial response, otherwise `Content-Length`.
+	fn total_length(&self) -> Option<u64> {
+		self.decoder.total
+	}
+}
+
+/// How failed attempts are retried, both when making the initial request and when resuming the body.
+///
+/// Attempts are counted, and elapsed time measured, from the first failure since the last successful progress; so a long download that occasionally drops its connection is never abandoned, while a dead host eventually is.
+///
+/// The delay before the `n`th retry is `base_delay * 2^(n-1)`, capped at `max_delay`. With jitter enabled (the default) a uniformly random delay between zero and that is used instead, to avoid many clients retrying in lockstep. If the server responded with a `Retry-After` header, the delay it requests is used instead, also capped at `max_delay`.
+///
+/// Besides connection errors, responses with a status of 408, 429, 500, 502, 503 or 504 are retried by default.
+///
+/// By default the body is only resumed if the server advertised support for byte ranges with `Accept-Ranges: bytes`; see [`probe_ranges()`](RetryPolicy::probe_ranges) for servers that support them without saying so, and [`restart_limit()`](RetryPolicy::restart_limit) for those that don't support them at all.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+	statuses: Vec<reqwest::StatusCode>,
+	max_attempts: Option<u32>,
+	max_elapsed: Option<Duration>,
+	base_delay: Duration,
+	max_delay: Duration,
+	jitter: bool,
+	probe_ranges: bool,
+	restart_limit: Option<u64>,
+}
+impl RetryPolicy {
+	/// Constructs a new `RetryPolicy` that retries indefinitely, backing off exponentially with jitter from 1 second up to 1 minute.
+	pub fn new() -> Self {
+		Self {
+			statuses: vec![
+				reqwest::StatusCode::REQUEST_TIMEOUT,
+				reqwest::StatusCode::TOO_MANY_REQUESTS,
+				reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+				reqwest::StatusCode::BAD_GATEWAY,
+				reqwest::StatusCode::SERVICE_UNAVAILABLE,
+				reqwest::StatusCode::GATEWAY_TIMEOUT,
+			],
+			max_attempts: None,
+			max_elapsed: None,
+			base_delay: Duration::from_secs(1),
+			max_delay: Duration::from_secs(60),
+			jitter: true,
+			probe_ranges: false,
+			restart_limit: None,
+		}
+	}
+	/// Set the response statuses that are retried. Only client and server error statuses (4xx and 5xx) are recognised.
+	pub fn retry_statuses<I>(mut self, statuses: I) -> Self
+	where
+		I: IntoIterator<Item = reqwest::StatusCode>,
+	{
+		self.statuses = statuses.into_iter().collect();
+		self
+	}
+	/// Give up after `max_attempts` consecutive failed attempts.
+	pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+		self.max_attempts = Some(max_attempts);
+		self
+	}
+	/// Give up rather than retry if the next attempt would start more than `max_elapsed` after the first of the consecutive failures.
+	pub fn max_elapsed(mut self, max_elapsed: Duration) -> Self {
+		self.max_elapsed = Some(max_elapsed);
+		self
+	}
+	/// Set the delay before the first retry.
+	pub fn base_delay(mut self, base_delay: Duration) -> Self {
+		self.base_delay = base_delay;
+		self
+	}
+	/// Set the maximum delay between retries. This also caps the delay requested by a server's `Retry-After` header.
+	pub fn max_delay(mut self, max_delay: Duration) -> Self {
+		self.max_delay = max_delay;
+		self
+	}
+	/// Enable or disable randomising the delay between retries.
+	pub fn jitter(mut self, jitter: bool) -> Self {
+		self.jitter = jitter;
+		self
+	}
+	/// Enable or disable resuming bodies whose response didn't include `Accept-Ranges: bytes`. Defaults to disabled, in which case the error is returned.
+	///
+	/// If enabled, a `Range` request is sent anyway. A `206 Partial Content` response continuing from the right offset is resumed from as usual; a `200 OK` response of the whole body is restarted from, discarding the bytes already delivered, within the [`restart_limit()`](RetryPolicy::restart_limit) if one is set; and anything else is returned as an error.
+	pub fn probe_ranges(mut self, probe_ranges: bool) -> Self {
+		self.probe_ranges = probe_ranges;
+		self
+	}
+	/// Resume bodies whose response didn't include `Accept-Ranges: bytes` by requesting the whole body again and discarding the bytes already delivered, provided no more than `restart_limit` bytes in total are discarded. Defaults to disabled, in which case the error is returned.
+	///
+	/// If [`probe_ranges()`](RetryPolicy::probe_ranges) is enabled, this instead limits the bytes discarded when the server responds to a probe with the whole body, beyond which an [`Error::InconsistentRange`] is returned. Without a limit, probing restarts however many bytes that discards.
+	pub fn restart_limit(mut self, restart_limit: u64) -> Self {
+		self.restart_limit = Some(restart_limit);
+		self
+	}
+	/// The error for `response`, if it has a status that should be retried.
+	fn retryable_status(&self, response: &reqwest::Response) -> Option<reqwest::Error> {
+		if !self.statuses.contains(&response.status()) {
+			return None;
+		}
+		response.error_for_status_ref().err()
+	}
+	fn delay(&self, retry: u32) -> Duration {
+		let factor = 2_u32.saturating_pow(retry.saturating_sub(1));
+		let delay = self
+			.base_delay
+			.checked_mul(factor)
+			.map_or(self.max_delay, |delay| delay.min(self.max_delay));
+		if self.jitter {
+			delay.mul_f64(rand::random())
+		} else {
+			delay
+		}
+	}
+}
+
+/// Tracks consecutive failures against a [`RetryPolicy`].
+#[derive(Debug)]
+struct Retry {
+	policy: RetryPolicy,
+	failures: u32,
+	first_failure: Option<Instant>,
+}
+impl Retry {
+	fn new(policy: RetryPolicy) -> Self {
+		Self {
+			policy,
+			failures: 0,
+			first_failure: None,
+		}
+	}
+	/// Record a failure, returning how long to wait before retrying, or `None` if the policy is exhausted. `retry_after` is the delay requested by the server, if any.
+	fn next_delay(&mut self, retry_after: Option<Duration>) -> Option<Duration> {
+		self.failures += 1;
+		let first_failure = *self.first_failure.get_or_insert_with(Instant::now);
+		if matches!(self.policy.max_attempts, Some(max_attempts) if self.failures >= max_attempts) {
+			return None;
+		}
+		let delay = retry_after.map_or_else(
+			|| self.policy.delay(self.failures),
+			|delay| delay.min(self.policy.max_delay),
+		);
+		if matches!(self.policy.max_elapsed, Some(max_elapsed) if first_failure.elapsed() + delay > max_elapsed)
+		{
+			return None;
+		}
+		Some(delay)
+	}
+	/// Record progress, resetting the count of consecutive failures.
+	fn reset(&mut self) {
+		self.failures = 0;
+		self.first_failure = None;
+	}
+}
+
+/// The validators of the initial response, used to check that resumed responses are of the same representation.
+#[derive(Clone, Debug)]
+struct Validator {
+	etag: Option<hyperx::header::EntityTag>,
+	last_modified: Option<hyperx::header::HttpDate>,
+}
+impl Validator {
+	fn from_headers(headers: &hyperx::Headers) -> Self {
+		let etag = headers
+			.get::<hyperx::header::ETag>()
+			.map(|hyperx::header::ETag(etag)| etag.clone());
+		let last_modified = headers
+			.get::<hyperx::header::LastModified>()
+			.map(|&hyperx::header::LastModified(date)| date);
+		Self {
+			etag,
+			last_modified,
+		}
+	}
+	/// `If-Range` requires a strong validator, so a weak `ETag` is only compared after the fact.
+	fn if_range(&self) -> Option<hyperx::header::IfRange> {
+		match (&self.etag, self.last_modified) {
+			(Some(etag), _) if !etag.weak => Some(hyperx::header::IfRange::EntityTag(etag.clone())),
+			(_, Some(date)) => Some(hyperx::header::IfRange::Date(date)),
+			_ => None,
+		}
+	}
+	/// Whether `other` could be the same representation as `self`.
+	fn matches(&self, other: &Self) -> bool {
+		let etag = match (&self.etag, &other.etag) {
+			(Some(a), Some(b)) => a.weak_eq(b),
+			_ => true,
+		};
+		let last_modified = match (self.last_modified, other.last_modified) {
+			(Some(a), Some(b)) => a == b,
+			_ => true,
+		};
+		etag && last_modified
+	}
+}
+
+/// The Errors that may occur when making or resuming a Request.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	/// An error from [`reqwest`].
+	Reqwest(reqwest::Error),
+	/// The request couldn't be built, for example because its URL or a header was invalid. It's shared as it's returned by every send of the [`RequestBuilder`].
+	Builder(Arc<reqwest::Error>),
+	/// The resource changed on the server between the initial request and a resume, so the body couldn't be continued.
+	ResourceChanged,
+	/// An I/O error, for example while writing a download to a file.
+	Io(io::Error),
+	/// An error deserializing the body as JSON.
+	#[cfg(feature = "json")]
+	Json(serde_json::Error),
+	/// The response had a status that, while not an error status, isn't one that was expected, for example a redirect that wasn't followed.
+	UnexpectedStatus(reqwest::StatusCode),
+	/// The server doesn't support the byte ranges needed, having responded to a range request with the whole body.
+	RangesUnsupported,
+	/// A resumed response didn't continue from the position the body had reached.
+	InconsistentRange {
+		/// The offset the response was expected to start at.
+		expected: u64,
+		/// The offset the response started at, per its `Content-Range` header, if known.
+		received: Option<u64>,
+	},
+	/// The body ended before all of it was received, per the range requested, or the `Content-Length` or `Content-Range` of a response.
+	Truncated {
+		/// The offset the body was expected to end at.
+		expected: u64,
+		/// The offset the body ended at.
+		received: u64,
+	},
+	/// The body didn't match the checksum it was verified against.
+	ChecksumMismatch {
+		/// The expected checksum.
+		expected: Checksum,
+		/// The checksum of the body received.
+		actual: Checksum,
+	},
+	/// The [`RetryPolicy`] gave up, after `attempts` consecutive failed attempts. The last failure is the `source`.
+	RetriesExhausted {
+		/// The number of consecutive failed attempts.
+		attempts: u32,
+		/// The error of the last attempt.
+		source: Box<Error>,
+	},
+	/// The response body failed, after `delivered` bytes of it had been yielded across `resumes` resumes. The failure is the `source`.
+	Body {
+		/// The number of bytes of the body yielded before it failed.
+		delivered: u64,
+		/// The number of times the body was resumed.
+		resumes: u32,
+		/// The error the body failed with.
+		source: Box<Error>,
+	},
+}
+impl Error {
+	/// Whether the resource changed on the server, so the body couldn't be resumed.
+	pub fn is_resource_changed(&self) -> bool {
+		matches!(self.root(), Self::ResourceChanged)
+	}
+	/// Whether the server doesn't support the byte ranges needed.
+	pub fn is_ranges_unsupported(&self) -> bool {
+		matches!(self.root(), Self::RangesUnsupported)
+	}
+	/// Whether a resumed response didn't continue from the position the body had reached.
+	pub fn is_inconsistent_range(&self) -> bool {
+		matches!(self.root(), Self::InconsistentRange { .. })
+	}
+	/// Whether the body ended before all of it was received.
+	pub fn is_truncated(&self) -> bool {
+		matches!(self.root(), Self::Truncated { .. })
+	}
+	/// Whether the body didn't match the checksum it was verified against.
+	pub fn is_checksum_mismatch(&self) -> bool {
+		matches!(self.root(), Self::ChecksumMismatch { .. })
+	}
+	/// Whether the [`RetryPolicy`] gave up retrying.
+	pub fn is_retries_exhausted(&self) -> bool {
+		match self {
+			Self::RetriesExhausted { .. } => true,
+			Self::Body { source, .. } => source.is_retries_exhausted(),
+			_ => false,
+		}
+	}
+	/// Whether the error is an I/O error.
+	pub fn is_io(&self) -> bool {
+		matches!(self.root(), Self::Io(_))
+	}
+	/// Whether the request couldn't be built.
+	///
+	/// See [`reqwest::Error::is_builder()`].
+	pub fn is_builder(&self) -> bool {
+		matches!(self.root(), Self::Builder(_))
+	}
+	/// Whether the error is from a timeout.
+	///
+	/// See [`reqwest::Error::is_timeout()`].
+	pub fn is_timeout(&self) -> bool {
+		matches!(self.root(), Self::Reqwest(err) if err.is_timeout())
+	}
+	/// Whether the error is related to connecting.
+	///
+	/// See [`reqwest::Error::is_connect()`].
+	pub fn is_connect(&self) -> bool {
+		matches!(self.root(), Self::Reqwest(err) if err.is_connect())
+	}
+	/// Whether the error is from a response with an error status.
+	///
+	/// See [`reqwest::Error::is_status()`].
+	pub fn is_status(&self) -> bool {
+		matches!(self.root(), Self::Reqwest(err) if err.is_status())
+	}
+	/// The status of the response, if the error is from an error or otherwise unexpected status.
+	///
+	/// See [`reqwest::Error::status()`].
+	pub fn status(&self) -> Option<reqwest::StatusCode> {
+		match self.root() {
+			Self::Reqwest(err) => err.status(),
+			Self::UnexpectedStatus(status) => Some(*status),
+			_ => None,
+		}
+	}
+	/// The number of bytes of the body yielded before it failed, if the error is from the body.
+	pub fn bytes_delivered(&self) -> Option<u64> {
+		match self {
+			Self::Body { delivered, .. } => Some(*delivered),
+			_ => None,
+		}
+	}
+	/// The number of times the body was resumed before it failed, if the error is from the body.
+	pub fn resumes(&self) -> Option<u32> {
+		match self {
+			Self::Body { resumes, .. } => Some(*resumes),
+			_ => None,
+		}
+	}
+	/// The underlying error, beneath any context of retries and the body.
+	fn root(&self) -> &Self {
+		match self {
+			Self::RetriesExhausted { source, .. } | Self::Body { source, .. } => source.root(),
+			err => err,
+		}
+	}
+}
+impl From<reqwest::Error> for Error {
+	fn from(err: reqwest::Error) -> Self {
+		Self::Reqwest(err)
+	}
+}
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+	fn from(err: serde_json::Error) -> Self {
+		Self::Json(err)
+	}
+}
+impl From<io::Error> for Error {
+	fn from(err: io::Error) -> Self {
+		Self::Io(err)
+	}
+}
+impl From<Error> for io::Error {
+	fn from(err: Error) -> Self {
+		let kind = match err.root() {
+			Error::Io(err) => err.kind(),
+			Error::Reqwest(err) if err.is_timeout() => io::ErrorKind::TimedOut,
+			Error::Builder(_) => io::ErrorKind::InvalidInput,
+			Error::UnexpectedStatus(_) => io::ErrorKind::InvalidData,
+			Error::RangesUnsupported => io::ErrorKind::Unsupported,
+			_ => io::ErrorKind::Other,
+		};
+		Self::new(kind, err)
+	}
+}
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Reqwest(err) => err.fmt(f),
+			Self::Builder(err) => err.fmt(f),
+			Self::Io(err) => err.fmt(f),
+			#[cfg(feature = "json")]
+			Self::Json(err) => err.fmt(f),
+			Self::ResourceChanged => f.write_str("resource changed on the server while resuming"),
+			Self::UnexpectedStatus(status) => write!(f, "unexpected status {status}"),
+			Self::RangesUnsupported => f.write_str("server doesn't support byte ranges"),
+			Self::InconsistentRange {
+				expected,
+				received: Some(received),
+			} => write!(
+				f,
+				"resumed response started at byte {received} rather than byte {expected}"
+			),
+			Self::InconsistentRange {
+				expected,
+				received: None,
+			} => write!(
+				f,
+				"resumed response didn't indicate it started at byte {expected}"
+			),
+			Self::Truncated { expected, received } => write!(
+				f,
+				"body ended at byte {received} rather than byte {expected}"
+			),
+			Self::ChecksumMismatch { expected, actual } => {
+				write!(f, "body checksum {actual} didn't match {expected}")
+			}
+			Self::RetriesExhausted { attempts, .. } => {
+				write!(f, "gave up after {attempts} failed attempts")
+			}
+			Self::Body {
+				delivered, resumes, ..
+			} => write!(
+				f,
+				"response body failed after {delivered} bytes and {resumes} resumes"
+			),
+		}
+	}
+}
+impl error::Error for Error {
+	fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+		match self {
+			Self::Reqwest(err) => Some(err),
+			Self::Builder(err) => Some(&**err),
+			Self::Io(err) => Some(err),
+			#[cfg(feature = "json")]
+			Self::Json(err) => Some(err),
+			Self::RetriesExhausted { source, .. } | Self::Body { source, .. } => Some(&**source),
+			Self::ResourceChanged
+			| Self::UnexpectedStatus(_)
+			| Self::RangesUnsupported
+			| Self::InconsistentRange { .. }
+			| Self::Truncated { .. }
+			| Self::ChecksumMismatch { .. } => None,
 		}
 	}
 }
 
+/// A `Result` alias where the `Err` case is [`reqwest_resume::Error`](Error).
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
 struct Decoder {
 	client: reqwest::Client,
-	method: reqwest::Method,
-	url: reqwest::Url,
-	decoder: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send + Unpin>>,
+	request: reqwest::Request,
+	state: State,
 	accept_byte_ranges: bool,
+	validator: Validator,
+	retry: Retry,
 	pos: u64,
+	end: Option<u64>,
+	skip: u64,
+	restarted: u64,
+	tracker: Tracker,
+	total: Option<u64>,
+	/// The offset the body of the current response should end at, if known.
+	body_end: Option<u64>,
+	verifier: Option<Verifier>,
+	decompressor: Option<Decompressor>,
+	observer: Observer,
+	trace: Trace,
+}
+impl fmt::Debug for Decoder {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Decoder")
+			.field("method", self.request.method())
+			.field("url", self.request.url())
+			.field("pos", &self.pos)
+			.field("end", &self.end)
+			.finish_non_exhaustive()
+	}
+}
+enum State {
+	Body(BoxStream<'static, reqwest::Result<Bytes>>),
+	Resume(BoxFuture<'static, reqwest::Result<reqwest::Response>>),
+	/// The whole body has been received, but not yet verified.
+	Complete,
+	Done,
+}
+impl Decoder {
+	fn resume(
+		&self, delay: Duration,
+	) -> impl Future<Output = reqwest::Result<reqwest::Response>> + Send {
+		let mut request = self.request.clone_buffered();
+		if self.ranges() {
+			request.headers_mut().extend(range_headers(
+				ByteRange::FromTo(self.pos, self.end),
+				Some(&self.validator),
+			));
+		}
+		// https://developer.mozilla.org/en-US/docs/Web/HTTP/Range_requests
+		// https://github.com/sdroege/gst-plugin-rs/blob/dcb36832329fde0113a41b80ebdb5efd28ead68d/gst-plugin-http/src/httpsrc.rs
+		let client = self.client.clone();
+		self.trace
+			.instrument(sleep(delay).then(move |()| client.execute(request)))
+	}
+	fn emit(&self, event: &Event) {
+		self.observer.emit(event);
+		self.trace.event(event);
+	}
+	/// Whether to resume with a `Range` request, rather than restarting from the beginning.
+	fn ranges(&self) -> bool {
+		self.accept_byte_ranges || self.retry.policy.probe_ranges
+	}
+	/// Check the resumed response continues the body, returning the number of already-delivered bytes at the start of it that need to be skipped.
+	fn check(&mut self, response: &reqwest::Response) -> Result<u64> {
+		let _ = response.error_for_status_ref()?;
+		let headers = hyperx::Headers::from(response.headers());
+		if !self.validator.matches(&Validator::from_headers(&headers)) {
+			return Err(Error::ResourceChanged);
+		}
+		match response.status() {
+			reqwest::StatusCode::PARTIAL_CONTENT => {
+				let start = content_range_start(&headers);
+				if start != Some(self.pos) {
+					return Err(Error::InconsistentRange {
+						expected: self.pos,
+						received: start,
+					});
+				}
+				Ok(0)
+			}
+			// The server ignored the Range header and sent the whole body
+			reqwest::StatusCode::OK => {
+				if !self.accept_byte_ranges && self.retry.policy.probe_ranges {
+					// Restarting in response to a probe is limited like any other restart, if a limit is set
+					let restarted = self.restarted + self.pos;
+					if matches!(self.retry.policy.restart_limit, Some(limit) if restarted > limit) {
+						return Err(Error::InconsistentRange {
+							expected: self.pos,
+							received: Some(0),
+						});
+					}
+					self.restarted = restarted;
+				}
+				Ok(self.pos)
+			}
+			_ => Err(Error::InconsistentRange {
+				expected: self.pos,
+				received: None,
+			}),
+		}
+	}
+	/// Handle the body failing partway through, resuming it if possible, or otherwise finishing and returning `err` to be yielded.
+	fn fail(&mut self, err: Error) -> Option<Error> {
+		if !self.ranges() {
+			// Restarting re-downloads the bytes already delivered
+			let restarted = self.restarted + self.pos;
+			if !matches!(self.retry.policy.restart_limit, Some(limit) if restarted <= limit) {
+				self.emit(&Event::Error { error: &err });
+				self.state = State::Done;
+				return Some(err);
+			}
+			self.restarted = restarted;
+		}
+		self.retry(err, None)
+	}
+	/// Finish the whole body, verifying its checksum if there is one.
+	fn complete(&mut self) -> Result<()> {
+		if let Some(verifier) = self.verifier.take() {
+			if let Err(err) = verifier.verify() {
+				self.emit(&Event::Error { error: &err });
+				return Err(err);
+			}
+		}
+		let stats = self.tracker.finish(self.request.url());
+		self.emit(&Event::Completed {
+			pos: self.pos,
+			stats,
+		});
+		Ok(())
+	}
+	/// Schedule a resume per the [`RetryPolicy`], or if it's exhausted finish, returning `err` to be yielded.
+	fn retry(&mut self, err: Error, retry_after: Option<Duration>) -> Option<Error> {
+		self.emit(&Event::Error { error: &err });
+		if let Some(delay) = self.retry.next_delay(retry_after) {
+			self.emit(&Event::ResumeScheduled {
+				pos: self.pos,
+				delay,
+			});
+			self.trace.attempt(self.pos);
+			self.state = State::Resume(Box::pin(self.resume(delay)));
+			self.tracker.stats.attempts += 1;
+			self.tracker.stats.resumes += 1;
+			self.tracker.stats.backoff += delay;
+			None
+		} else {
+			self.state = State::Done;
+			Some(Error::RetriesExhausted {
+				attempts: self.retry.failures,
+				source: Box::new(err),
+			})
+		}
+	}
 }
 impl Stream for Decoder {
-	type Item = reqwest::Result<Bytes>;
+	type Item = Result<Bytes>;
 
 	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+		let this = &mut *self;
+		let item = ready!(this.poll_decompressed(cx));
+		if let State::Done = this.state {
+			let _ = this.tracker.finish(this.request.url());
+		}
+		Poll::Ready(item.map(|item| {
+			item.map_err(|err| Error::Body {
+				delivered: this.tracker.stats.bytes,
+				resumes: this.tracker.stats.resumes,
+				source: Box::new(err),
+			})
+		}))
+	}
+}
+impl Decoder {
+	/// Poll the body, decompressed if it's to be.
+	fn poll_decompressed(&mut self, cx: &mut Context) -> Poll<Option<Result<Bytes>>> {
 		loop {
-			match ready!(self.decoder.as_mut().poll_next(cx)) {
-				Some(Err(err)) => {
-					if !self.accept_byte_ranges {
-						// TODO: we could try, for those servers that don't output Accept-Ranges but work anyway
-						break Poll::Ready(Some(Err(err)));
-					}
-					let builder = self.client.request(self.method.clone(), self.url.clone());
-					let mut headers = hyperx::Headers::new();
-					headers.set(hyperx::header::Range::Bytes(vec![
-						hyperx::header::ByteRangeSpec::AllFrom(self.pos),
-					]));
-					let builder = builder.headers(headers.into());
-					// https://developer.mozilla.org/en-US/docs/Web/HTTP/Range_requests
-					// https://github.com/sdroege/gst-plugin-rs/blob/dcb36832329fde0113a41b80ebdb5efd28ead68d/gst-plugin-http/src/httpsrc.rs
-					self.decoder = Box::pin(
-						sleep(Duration::from_secs(1))
-							.then(|()| builder.send())
-							.map_ok(reqwest::Response::bytes_stream)
-							.try_flatten_stream(),
-					);
+			let item = ready!(self.poll_body(cx));
+			let decompressed = match (&mut self.decompressor, item) {
+				(Some(decompressor), Some(Ok(chunk))) => decompressor.decompress(&chunk),
+				(Some(_), None) => self.decompressor.take().unwrap().finish(),
+				(_, item) => break Poll::Ready(item),
+			};
+			match decompressed {
+				Ok(chunk) if !chunk.is_empty() => break Poll::Ready(Some(Ok(chunk))),
+				Ok(_) => (),
+				Err(err) => {
+					self.state = State::Done;
+					self.decompressor = None;
+					break Poll::Ready(Some(Err(err.into())));
 				}
-				Some(Ok(n)) => {
-					self.pos += n.len() as u64;
-					break Poll::Ready(Some(Ok(n)));
+			}
+		}
+	}
+	fn poll_body(&mut self, cx: &mut Context) -> Poll<Option<Result<Bytes>>> {
+		loop {
+			match &mut self.state {
+				State::Body(decoder) => match ready!(decoder.as_mut().poll_next(cx)) {
+					Some(Err(err)) => {
+						if let Some(err) = self.fail(err.into()) {
+							break Poll::Ready(Some(Err(err)));
+						}
+					}
+					Some(Ok(mut n)) => {
+						if self.skip > 0 {
+							let skip = self.skip.min(n.len() as u64);
+							self.skip -= skip;
+							self.tracker.stats.wasted += skip;
+							#[allow(clippy::cast_possible_truncation)]
+							n.advance(skip as usize);
+							if n.is_empty() {
+								continue;
+							}
+						}
+						if let Some(end) = self.end {
+							// The server may have sent more than was requested
+							#[allow(clippy::cast_possible_truncation)]
+							n.truncate(end.saturating_sub(self.pos).min(n.len() as u64) as usize);
+							if self.pos + n.len() as u64 == end {
+								self.state = State::Complete;
+							}
+							if n.is_empty() {
+								continue;
+							}
+						}
+						if let Some(verifier) = &mut self.verifier {
+							verifier.update(&n);
+						}
+						self.pos += n.len() as u64;
+						self.tracker.stats.bytes += n.len() as u64;
+						self.retry.reset();
+						self.emit(&Event::Received {
+							pos: self.pos,
+							total: self.total,
+						});
+						break Poll::Ready(Some(Ok(n)));
+					}
+					None => {
+						// The connection may have been closed cleanly partway through, which only the lengths given by the responses can tell apart from the end of the body
+						let end = match (self.end, self.total) {
+							// The range requested may extend past the end of the body
+							(Some(end), Some(total)) => Some(end.min(total)),
+							(end, total) => end.or(total),
+						};
+						if let Some(expected) = end.or(self.body_end).filter(|&end| self.pos < end)
+						{
+							let err = Error::Truncated {
+								expected,
+								received: self.pos,
+							};
+							if let Some(err) = self.fail(err) {
+								break Poll::Ready(Some(Err(err)));
+							}
+							continue;
+						}
+						self.state = State::Complete;
+					}
+				},
+				State::Resume(resume) => match ready!(resume.as_mut().poll(cx)) {
+					Ok(response) => {
+						if let Some(err) = self.retry.policy.retryable_status(&response) {
+							let retry_after = retry_after(response.headers());
+							if let Some(err) = self.retry(err.into(), retry_after) {
+								break Poll::Ready(Some(Err(err)));
+							}
+							continue;
+						}
+						match self.check(&response) {
+							Ok(skip) => {
+								self.emit(&Event::Resumed { pos: self.pos });
+								self.skip = skip;
+								// The initial response may not have given the length
+								self.total = self.total.or_else(|| total_length(&response));
+								self.body_end = body_end(&response);
+								self.state = State::Body(Box::pin(response.bytes_stream()));
+							}
+							Err(err) => {
+								self.emit(&Event::Error { error: &err });
+								self.state = State::Done;
+								break Poll::Ready(Some(Err(err)));
+							}
+						}
+					}
+					Err(err) => {
+						if let Some(err) = self.retry(err.into(), None) {
+							break Poll::Ready(Some(Err(err)));
+						}
+					}
+				},
+				State::Complete => {
+					self.state = State::Done;
+					break Poll::Ready(self.complete().err().map(Err));
 				}
-				None => break Poll::Ready(None),
+				State::Done => break Poll::Ready(None),
+			}
+		}
+	}
+}
+
+/// Check that `request` can be built, keeping the error if not so that it's returned by every send.
+fn built(
+	request: reqwest::RequestBuilder,
+) -> std::result::Result<reqwest::RequestBuilder, Arc<reqwest::Error>> {
+	// Bodies can only be set from `Bytes`, so this only fails to clone if building fails
+	match request.try_clone() {
+		Some(_) => Ok(request),
+		None => Err(Arc::new(request.build().expect_err(
+			"requests with buffered bodies only fail to clone if they hold an error",
+		))),
+	}
+}
+
+/// Extension to [`reqwest::Request`] and [`reqwest::RequestBuilder`] to clone requests whose body is buffered.
+trait RequestExt {
+	fn clone_buffered(&self) -> Self;
+}
+impl RequestExt for reqwest::Request {
+	fn clone_buffered(&self) -> Self {
+		// Bodies can only be set from `Bytes`, so are never streams
+		self.try_clone()
+			.expect("request bodies are buffered, so can be cloned")
+	}
+}
+impl RequestExt for reqwest::RequestBuilder {
+	fn clone_buffered(&self) -> Self {
+		// Builders that hold an error are kept as the error instead, by `built()`
+		self.try_clone()
+			.expect("request bodies are buffered, so can be cloned")
+	}
+}
+
+/// A range of bytes to request.
+#[derive(Copy, Clone, Debug)]
+enum ByteRange {
+	/// The bytes from `start` up to but excluding `end`, or to the end of the body if `None`.
+	FromTo(u64, Option<u64>),
+	/// The last `n` bytes.
+	Last(u64),
+}
+
+/// The offset the body of `response` to a request for `range` starts at, the offset it ends at if known, and the number of bytes at its start to be skipped.
+fn position(
+	range: Option<ByteRange>, if_range: Option<&Validator>, response: &reqwest::Response,
+	validator: &Validator,
+) -> Result<(u64, Option<u64>, u64)> {
+	Ok(match range {
+		Some(range) if response.status() == reqwest::StatusCode::PARTIAL_CONTENT => {
+			let received = content_range_start(&hyperx::Headers::from(response.headers()));
+			let (start, end) = match range {
+				ByteRange::FromTo(start, end) => (start, end),
+				// The start of a suffix depends on the length, so is taken from the response
+				ByteRange::Last(_) => (received.unwrap_or(0), None),
+			};
+			if received != Some(start) {
+				return Err(Error::InconsistentRange {
+					expected: start,
+					received,
+				});
 			}
+			(start, end, 0)
+		}
+		// The range starts at the end of the body, so is empty
+		Some(ByteRange::FromTo(start, _))
+			if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+				&& total_length(response) == Some(start) =>
+		{
+			(start, Some(start), 0)
+		}
+		// Anything but the whole body is an error, rather than the resource
+		Some(_) if response.status() != reqwest::StatusCode::OK => {
+			let _ = response.error_for_status_ref()?;
+			return Err(Error::UnexpectedStatus(response.status()));
+		}
+		// The representation changed, so If-Range got us the whole of the new one
+		Some(_) if if_range.is_some_and(|if_range| !if_range.matches(validator)) => (0, None, 0),
+		// The server ignored the Range header and sent the whole body
+		Some(ByteRange::FromTo(start, end)) => (start, end, start),
+		Some(ByteRange::Last(n)) => {
+			// The server ignored the suffix range, and without the length of the body its start is unknown
+			let length = response.content_length().ok_or(Error::RangesUnsupported)?;
+			let start = length.saturating_sub(n);
+			(start, None, start)
+		}
+		None => (0, None, 0),
+	})
+}
+
+/// Whether the server advertises support for byte ranges with `Accept-Ranges`.
+#[allow(clippy::needless_borrowed_reference, clippy::manual_contains)]
+fn accept_byte_ranges(headers: &hyperx::Headers) -> bool {
+	if let Some(&hyperx::header::AcceptRanges(ref ranges)) = headers.get() {
+		ranges
+			.iter()
+			.any(|u| *u == hyperx::header::RangeUnit::Bytes)
+	} else {
+		false
+	}
+}
+
+/// The length of the whole representation, per the `Content-Range` header if `response` is partial or its range unsatisfiable, otherwise `Content-Length`.
+fn total_length(response: &reqwest::Response) -> Option<u64> {
+	if response.status() != reqwest::StatusCode::PARTIAL_CONTENT
+		&& response.status() != reqwest::StatusCode::RANGE_NOT_SATISFIABLE
+	{
+		return response.content_length();
+	}
+	match hyperx::Headers::from(response.headers()).get() {
+		Some(hyperx::header::ContentRange(hyperx::header::ContentRangeSpec::Bytes {
+			instance_length,
+			..
+		})) => *instance_length,
+		_ => None,
+	}
+}
+
+/// The offset the body of `response` ends at, per its `Content-Range` header if it's partial, otherwise its `Content-Length`.
+fn body_end(response: &reqwest::Response) -> Option<u64> {
+	if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+		return response.content_length();
+	}
+	match hyperx::Headers::from(response.headers()).get() {
+		Some(hyperx::header::ContentRange(hyperx::header::ContentRangeSpec::Bytes {
+			range: Some((_, end)),
+			..
+		})) => Some(end + 1),
+		_ => None,
+	}
+}
+
+/// The `Range` and `If-Range` headers to request `range`, provided it's still the representation identified by `validator`.
+fn range_headers(range: ByteRange, validator: Option<&Validator>) -> reqwest::header::HeaderMap {
+	let mut headers = hyperx::Headers::new();
+	headers.set(hyperx::header::Range::Bytes(vec![match range {
+		ByteRange::FromTo(start, Some(end)) => {
+			hyperx::header::ByteRangeSpec::FromTo(start, end - 1)
 		}
+		ByteRange::FromTo(start, None) => hyperx::header::ByteRangeSpec::AllFrom(start),
+		ByteRange::Last(n) => hyperx::header::ByteRangeSpec::Last(n),
+	}]));
+	if let Some(if_range) = validator.and_then(Validator::if_range) {
+		headers.set(if_range);
+	}
+	headers.into()
+}
+
+/// The offset of the first byte of a `206 Partial Content` response, per its `Content-Range` header.
+fn content_range_start(headers: &hyperx::Headers) -> Option<u64> {
+	match headers.get() {
+		Some(hyperx::header::ContentRange(hyperx::header::ContentRangeSpec::Bytes {
+			range: Some((start, _)),
+			..
+		})) => Some(*start),
+		_ => None,
+	}
+}
+
+/// The delay requested by a `Retry-After` header, if present.
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+	match hyperx::Headers::from(headers).get()? {
+		hyperx::header::RetryAfter::Delay(delay) => Some(*delay),
+		hyperx::header::RetryAfter::DateTime(date) => Some(
+			SystemTime::from(*date)
+				.duration_since(SystemTime::now())
+				.unwrap_or_default(),
+		),
 	}
 }
 
 /// Shortcut method to quickly make a GET request.
 ///
 /// See [`reqwest::get`].
-pub fn get(url: reqwest::Url) -> impl Future<Output = reqwest::Result<Response>> + Send {
-	// <T: IntoUrl>
+pub fn get<U>(url: U) -> impl Future<Output = Result<Response>> + Send
+where
+	U: reqwest::IntoUrl,
+{
 	Client::new().get(url).send()
 }
 
 #[cfg(test)]
 mod test {
 	use async_compression::futures::bufread::GzipDecoder; // TODO: use stream or https://github.com/alexcrichton/flate2-rs/pull/214
-	use futures::{future::join_all, io::BufReader, AsyncBufReadExt, StreamExt, TryStreamExt};
-	use std::io;
+	use bytes::Bytes;
+	use futures::{
+		future::join_all, io::BufReader, stream, AsyncBufReadExt, StreamExt, TryStreamExt
+	};
+	use hyper::{
+		service::{make_service_fn, service_fn}, Body, Request, Response, Server
+	};
+	use std::{
+		convert::Infallible, io, sync::{
+			atomic::{AtomicUsize, Ordering}, Arc
+		}, time::{Duration, Instant}
+	};
+
+	const BODY: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+	/// Serve `handler` on an ephemeral local port. `handler` is also passed the index of the request.
+	fn serve<F>(handler: F) -> reqwest::Url
+	where
+		F: Fn(usize, &Request<Body>) -> Response<Body> + Send + Sync + 'static,
+	{
+		let handler = Arc::new(handler);
+		let count = Arc::new(AtomicUsize::new(0));
+		let make_service = make_service_fn(move |_| {
+			let (handler, count) = (handler.clone(), count.clone());
+			async move {
+				Ok::<_, Infallible>(service_fn(move |req| {
+					let i = count.fetch_add(1, Ordering::SeqCst);
+					let response = handler(i, &req);
+					async move { Ok::<_, Infallible>(response) }
+				}))
+			}
+		});
+		let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+		let url = format!("http://{}/", server.local_addr()).parse().unwrap();
+		drop(tokio::spawn(server));
+		url
+	}
+
+	/// A body that aborts the connection after `n` bytes of `body` have been sent.
+	fn truncated(body: &'static [u8], n: usize) -> Body {
+		let head = stream::once(async move { Ok(Bytes::from_static(&body[..n])) });
+		let abort = stream::once(async {
+			tokio::time::delay_for(Duration::from_millis(100)).await;
+			Err(io::Error::other("truncated"))
+		});
+		Body::wrap_stream(head.chain(abort))
+	}
+
+	/// A response to `req` honouring its Range header, if any, against `body`.
+	fn partial(body: &'static [u8], req: &Request<Body>) -> Response<Body> {
+		let range = req.headers().get("range").map(|range| {
+			let (start, end) = range.to_str().unwrap()["bytes=".len()..]
+				.split_once('-')
+				.unwrap();
+			match (start.parse::<usize>(), end.parse::<usize>()) {
+				(Ok(start), Ok(end)) => (start, end.min(body.len() - 1) + 1),
+				(Ok(start), Err(_)) => (start, body.len()),
+				(Err(_), Ok(suffix)) => (body.len() - suffix.min(body.len()), body.len()),
+				(Err(_), Err(_)) => panic!(),
+			}
+		});
+		let response = Response::builder().header("accept-ranges", "bytes");
+		match range {
+			Some((start, end)) => response
+				.status(206)
+				.header(
+					"content-range",
+					format!("bytes {}-{}/{}", start, end - 1, body.len()),
+				)
+				.body(Body::from(&body[start..end])),
+			None => response.body(Body::from(body)),
+		}
+		.unwrap()
+	}
+
+	/// A handler that fails its first response after 10 bytes of `body`, and then responds as [`partial()`].
+	fn flaky(
+		body: &'static [u8],
+	) -> impl Fn(usize, &Request<Body>) -> Response<Body> + Send + Sync {
+		move |i, req| {
+			if i == 0 {
+				return Response::builder()
+					.header("accept-ranges", "bytes")
+					.header("content-length", body.len())
+					.body(truncated(body, 10))
+					.unwrap();
+			}
+			partial(body, req)
+		}
+	}
+
+	#[tokio::test]
+	async fn resource_changed() {
+		let url = serve(|i, req| {
+			let etag = if i == 0 { "\"a\"" } else { "\"b\"" };
+			if i > 0 {
+				assert_eq!(req.headers()["if-range"], "\"a\"");
+			}
+			let body = if i == 0 {
+				truncated(BODY, 10)
+			} else {
+				Body::from(BODY)
+			};
+			Response::builder()
+				.header("accept-ranges", "bytes")
+				.header("content-length", BODY.len())
+				.header("etag", etag)
+				.body(body)
+				.unwrap()
+		});
+		let body = super::get(url).await.unwrap();
+		let chunks = body.bytes_stream().collect::<Vec<_>>().await;
+		assert_eq!(chunks.len(), 2);
+		assert_eq!(chunks[0].as_ref().unwrap(), &BODY[..10]);
+		let err = chunks[1].as_ref().unwrap_err();
+		assert!(err.is_resource_changed());
+		assert_eq!(err.bytes_delivered(), Some(10));
+		assert_eq!(err.resumes(), Some(1));
+	}
+
+	#[tokio::test]
+	async fn resume_full_response() {
+		// The server ignores the Range header, so the already-delivered bytes must be skipped
+		let url = serve(|i, _| {
+			let body = if i == 0 {
+				truncated(BODY, 10)
+			} else {
+				Body::from(BODY)
+			};
+			Response::builder()
+				.header("accept-ranges", "bytes")
+				.header("content-length", BODY.len())
+				.body(body)
+				.unwrap()
+		});
+		let body = super::get(url).await.unwrap();
+		let body = body
+			.bytes_stream()
+			.map_ok(|n| n.to_vec())
+			.try_concat()
+			.await
+			.unwrap();
+		assert_eq!(body, BODY);
+	}
+
+	#[tokio::test]
+	async fn probe_ranges() {
+		// The server supports ranges but doesn't send Accept-Ranges
+		let url = serve(|i, req| {
+			if i % 2 == 0 {
+				return Response::builder()
+					.header("content-length", BODY.len())
+					.body(truncated(BODY, 10))
+					.unwrap();
+			}
+			partial(BODY, req)
+		});
+		let policy = super::RetryPolicy::new().base_delay(Duration::from_millis(10));
+		let client = super::Client::new().retry_policy(policy.clone());
+		let body = client.get(url.clone()).send().await.unwrap();
+		assert!(body.bytes().await.is_err());
+		let client = client.retry_policy(policy.clone().probe_ranges(true));
+		let body = client.get(url).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+		// The server doesn't support ranges, so responds to the probe with the whole body
+		let handler = |i, _: &Request<Body>| {
+			if i == 0 {
+				return Response::builder()
+					.header("content-length", BODY.len())
+					.body(truncated(BODY, 10))
+					.unwrap();
+			}
+			Response::new(Body::from(BODY))
+		};
+		let body = client.get(serve(handler)).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+		let client = client.retry_policy(policy.clone().probe_ranges(true).restart_limit(10));
+		let body = client.get(serve(handler)).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+		let client = client.retry_policy(policy.probe_ranges(true).restart_limit(5));
+		let body = client.get(serve(handler)).send().await.unwrap();
+		assert!(body.bytes().await.unwrap_err().is_inconsistent_range());
+	}
+
+	#[tokio::test]
+	async fn restart_limit() {
+		// The server doesn't support ranges, and fails the first attempt partway through
+		let url = serve(|i, _| {
+			let body = if i % 2 == 0 {
+				truncated(BODY, 10)
+			} else {
+				Body::from(BODY)
+			};
+			Response::builder()
+				.header("content-length", BODY.len())
+				.body(body)
+				.unwrap()
+		});
+		let policy = super::RetryPolicy::new().base_delay(Duration::from_millis(10));
+		let client = super::Client::new().retry_policy(policy.clone().restart_limit(5));
+		let body = client.get(url.clone()).send().await.unwrap();
+		assert!(body.bytes().await.is_err());
+		let client = client.retry_policy(policy.restart_limit(10));
+		let body = client.get(url).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+	}
+
+	#[tokio::test]
+	async fn stats() {
+		use futures::AsyncReadExt;
+		// The first attempt is unavailable, and the second fails partway through a body that has to be restarted
+		let url = serve(|i, _| match i {
+			0 => Response::builder().status(503).body(Body::empty()).unwrap(),
+			1 => Response::builder()
+				.header("content-length", BODY.len())
+				.body(truncated(BODY, 10))
+				.unwrap(),
+			_ => Response::new(Body::from(BODY)),
+		});
+		let client = super::Client::new().retry_policy(
+			super::RetryPolicy::new()
+				.base_delay(Duration::from_millis(10))
+				.jitter(false)
+				.restart_limit(10),
+		);
+		let mut body = client.get(url).send().await.unwrap().into_async_read();
+		let mut buf = Vec::new();
+		let _ = body.read_to_end(&mut buf).await.unwrap();
+		assert_eq!(buf, BODY);
+		let stats = body.stats();
+		assert_eq!(
+			(stats.bytes, stats.attempts, stats.resumes, stats.wasted),
+			(36, 3, 1, 10)
+		);
+		assert_eq!(stats.backoff, Duration::from_millis(20));
+		assert!(stats.throughput() > 0.0);
+	}
+
+	#[tokio::test]
+	async fn truncated_body() {
+		// The first response ends cleanly, but short of the length in its Content-Range
+		let handler = |i, req: &Request<Body>| {
+			if i == 0 {
+				return Response::builder()
+					.status(206)
+					.header("accept-ranges", "bytes")
+					.header("content-range", format!("bytes 0-35/{}", BODY.len()))
+					.body(Body::from(&BODY[..10]))
+					.unwrap();
+			}
+			partial(BODY, req)
+		};
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = client.get(serve(handler)).range(0..).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+		let client = client.retry_policy(super::RetryPolicy::new().max_attempts(1));
+		let err = client.get(serve(handler)).range(0..).send().await.unwrap();
+		let err = err.bytes().await.unwrap_err();
+		assert!(err.is_truncated());
+	}
 
+	#[tokio::test]
+	async fn premature_eof() {
+		let url = serve(|i, req| match i {
+			// The initial response doesn't give its length, and fails partway through
+			0 => Response::builder()
+				.header("accept-ranges", "bytes")
+				.body(truncated(BODY, 10))
+				.unwrap(),
+			// The resumed response ends cleanly, but short of the end of its Content-Range
+			1 => Response::builder()
+				.status(206)
+				.header("accept-ranges", "bytes")
+				.header("content-range", "bytes 10-35/*")
+				.body(Body::from(&BODY[10..20]))
+				.unwrap(),
+			// As does the next, short of the length in its Content-Range
+			2 => Response::builder()
+				.status(206)
+				.header("accept-ranges", "bytes")
+				.header("content-range", format!("bytes 20-29/{}", BODY.len()))
+				.body(Body::from(&BODY[20..30]))
+				.unwrap(),
+			_ => partial(BODY, req),
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let mut body = client.get(url).send().await.unwrap();
+		let mut received = Vec::new();
+		while let Some(chunk) = body.chunk().await.unwrap() {
+			received.extend_from_slice(&chunk);
+		}
+		assert_eq!(received, BODY);
+		assert_eq!(body.stats().resumes, 3);
+	}
+
+	#[tokio::test]
+	async fn accept_encoding() {
+		let url = serve(|_, req| {
+			Response::new(Body::from(
+				req.headers()["accept-encoding"].as_bytes().to_owned(),
+			))
+		});
+		let client = super::Client::new();
+		let body = client.get(url.clone()).send().await.unwrap();
+		assert_eq!(body.text().await.unwrap(), "identity");
+		let mut body = client.get(url).header("accept-encoding", "gzip");
+		assert_eq!(body.send().await.unwrap().text().await.unwrap(), "gzip");
+	}
+
+	#[cfg(feature = "gzip")]
+	#[tokio::test]
+	async fn decompress() {
+		use std::io::Write;
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder.write_all(BODY).unwrap();
+		let compressed: &'static [u8] = Box::leak(encoder.finish().unwrap().into_boxed_slice());
+		// The compressed body fails partway through, and is resumed
+		let flaky = flaky(compressed);
+		let url = serve(move |i, req| {
+			assert!(req.headers()["accept-encoding"]
+				.to_str()
+				.unwrap()
+				.contains("gzip"));
+			let mut response = flaky(i, req);
+			let _ = response
+				.headers_mut()
+				.insert("content-encoding", "gzip".parse().unwrap());
+			response
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = client.get(url).decompress(true).send().await.unwrap();
+		assert_eq!(body.content_length(), Some(compressed.len() as u64));
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+	}
+
+	#[cfg(any(feature = "gzip", feature = "zstd", feature = "bzip2", feature = "xz"))]
+	#[tokio::test]
+	async fn decompressed() {
+		#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+		use std::io::Write;
+		let codecs = vec![
+			#[cfg(feature = "gzip")]
+			(super::Codec::Gzip, "gz", {
+				let mut gzip =
+					flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+				gzip.write_all(BODY).unwrap();
+				gzip.finish().unwrap()
+			}),
+			#[cfg(feature = "zstd")]
+			(
+				super::Codec::Zstd,
+				"zst",
+				zstd::encode_all(BODY, 0).unwrap(),
+			),
+			#[cfg(feature = "bzip2")]
+			(super::Codec::Bzip2, "bz2", {
+				let mut bzip2 =
+					bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+				bzip2.write_all(BODY).unwrap();
+				bzip2.finish().unwrap()
+			}),
+			#[cfg(feature = "xz")]
+			(super::Codec::Xz, "xz", {
+				let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+				xz.write_all(BODY).unwrap();
+				xz.finish().unwrap()
+			}),
+		];
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		for (codec, extension, compressed) in codecs {
+			let compressed: &'static [u8] = Box::leak(compressed.into_boxed_slice());
+			// The compressed body fails partway through, and is resumed
+			let url = serve(flaky(compressed));
+			let body = client.get(url.clone()).send().await.unwrap();
+			assert_eq!(body.decompressed(codec).bytes().await.unwrap(), BODY);
+			let body = client.get(url).send().await.unwrap();
+			assert_eq!(body.decompressed_auto().bytes().await.unwrap(), BODY);
+			// Compressed data that's cut short fails, rather than decompressing to part of the body
+			let url =
+				serve(move |_, _| Response::new(Body::from(&compressed[..compressed.len() / 2])));
+			let body = client.get(url).send().await.unwrap();
+			assert!(body.decompressed(codec).bytes().await.is_err());
+			let path = format!("file.{extension}");
+			assert_eq!(super::Codec::from_extension(&path), Some(codec));
+		}
+	}
+
+	#[tokio::test]
+	async fn lines() {
+		static TEXT: &[u8] = b"a\nbb\r\ncc\ndddd\n\ne";
+		// The body fails partway through a line, and is resumed
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = client.get(serve(flaky(TEXT))).send().await.unwrap();
+		let lines = body.lines().try_collect::<Vec<_>>().await.unwrap();
+		assert_eq!(lines, ["a", "bb", "cc", "dddd", "", "e"]);
+		let body = client.get(serve(flaky(TEXT))).send().await.unwrap();
+		let records = body.records(b'c').try_collect::<Vec<_>>().await.unwrap();
+		assert_eq!(records, ["a\nbb\r\n", "", "\ndddd\n\ne"]);
+	}
+
+	#[tokio::test]
+	async fn checksum() {
+		use sha2::Digest;
+		let md5 = md5::Md5::digest(BODY);
+		let sha256: [u8; 32] = sha2::Sha256::digest(BODY).into();
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		// Content-MD5, verified across a resume
+		let content_md5 = base64::encode(md5);
+		let flaky = flaky(BODY);
+		let url = serve(move |i, req| {
+			let mut response = flaky(i, req);
+			let _ = response
+				.headers_mut()
+				.insert("content-md5", content_md5.parse().unwrap());
+			response
+		});
+		let mut body = client.get(url).checksum(super::Checksum::Headers);
+		assert_eq!(body.send().await.unwrap().bytes().await.unwrap(), BODY);
+		// A wrong Repr-Digest
+		let url = serve(|_, _| {
+			let digest = base64::encode(sha2::Sha256::digest(b"other"));
+			Response::builder()
+				.header("repr-digest", format!("sha-256=:{digest}:"))
+				.body(Body::from(BODY))
+				.unwrap()
+		});
+		let mut body = client.get(url).checksum(super::Checksum::Headers);
+		let err = body.send().await.unwrap().bytes().await.unwrap_err();
+		assert!(err.is_checksum_mismatch());
+		// An S3 single-part ETag, and a user-supplied SHA-256
+		let etag = format!("\"{}\"", hex::encode(md5));
+		let url = serve(move |_, _| {
+			Response::builder()
+				.header("etag", &etag)
+				.body(Body::from(BODY))
+				.unwrap()
+		});
+		let mut body = client.get(url.clone()).checksum(super::Checksum::S3Etag);
+		assert_eq!(body.send().await.unwrap().bytes().await.unwrap(), BODY);
+		let mut body = client
+			.get(url.clone())
+			.checksum(super::Checksum::Sha256(sha256));
+		assert_eq!(body.send().await.unwrap().bytes().await.unwrap(), BODY);
+		let mut body = client.get(url).checksum(super::Checksum::Sha256([0; 32]));
+		let err = body.send().await.unwrap().bytes().await.unwrap_err();
+		assert!(err.is_checksum_mismatch());
+		// An opaque ETag that happens to look like an MD5 digest is only used if asked to
+		let url = serve(|_, _| {
+			Response::builder()
+				.header("etag", format!("\"{}\"", "0".repeat(32)))
+				.body(Body::from(BODY))
+				.unwrap()
+		});
+		let mut body = client.get(url.clone()).checksum(super::Checksum::Headers);
+		assert_eq!(body.send().await.unwrap().bytes().await.unwrap(), BODY);
+		let mut body = client.get(url).checksum(super::Checksum::S3Etag);
+		let err = body.send().await.unwrap().bytes().await.unwrap_err();
+		assert!(err.is_checksum_mismatch());
+	}
+
+	#[tokio::test]
+	async fn idempotency() {
+		let url = serve(|i, req| {
+			assert_eq!(req.method(), "POST");
+			if i < 2 {
+				return Response::builder()
+					.header("accept-ranges", "bytes")
+					.header("content-length", BODY.len())
+					.body(truncated(BODY, 10))
+					.unwrap();
+			}
+			assert_eq!(req.headers()["idempotency-key"], "abc");
+			partial(BODY, req)
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		// POST isn't resumed by default
+		let body = client.post(url.clone()).body("query").send().await.unwrap();
+		assert!(body.bytes().await.is_err());
+		let body = client
+			.post(url)
+			.body("query")
+			.idempotency_key("abc")
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+	}
+
+	#[tokio::test]
+	async fn request_template() {
+		let flaky = flaky(BODY);
+		let url = serve(move |i, req| {
+			// Every attempt carries the same headers and query
+			assert_eq!(req.uri().query(), Some("a=1"));
+			assert_eq!(req.headers()["authorization"], "Bearer token");
+			assert_eq!(req.headers()["x-custom"], "value");
+			flaky(i, req)
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = client
+			.get(url)
+			.query(&[("a", "1")])
+			.bearer_auth("token")
+			.header("x-custom", "value")
+			.timeout(Duration::from_secs(10))
+			.send()
+			.await
+			.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+	}
+
+	#[tokio::test]
+	async fn into_url() {
+		let url = serve(|_, _| Response::new(Body::from("body")));
+		let body = super::get(url.as_str()).await.unwrap();
+		assert_eq!(body.text().await.unwrap(), "body");
+		let err = super::get("not a url").await.unwrap_err();
+		assert!(err.is_builder());
+		let client = super::Client::new();
+		// Every send fails with the same error
+		let mut request = client.get("not a url");
+		let err = request.send().await.unwrap_err().to_string();
+		assert_eq!(request.send().await.unwrap_err().to_string(), err);
+		let body = client.segmented("not a url").bytes_stream();
+		assert!(body.map_ok(|n| n.to_vec()).try_concat().await.is_err());
+	}
+
+	#[tokio::test]
+	async fn observer() {
+		let url = serve(flaky(BODY));
+		let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+		let events_ = events.clone();
+		let client = super::Client::new()
+			.retry_policy(
+				super::RetryPolicy::new()
+					.base_delay(Duration::from_millis(10))
+					.jitter(false),
+			)
+			.observer(move |event| {
+				let event = match event {
+					super::Event::Connected { status, pos, total } => {
+						format!("connected {status} {pos} {total:?}")
+					}
+					super::Event::Received { pos, total } => format!("received {pos} {total:?}"),
+					super::Event::Error { .. } => "error".to_owned(),
+					super::Event::RetryScheduled { delay } => format!("retry {delay:?}"),
+					super::Event::ResumeScheduled { pos, delay } => {
+						format!("resume {pos} {delay:?}")
+					}
+					super::Event::Resumed { pos } => format!("resumed {pos}"),
+					super::Event::Completed { pos, .. } => format!("completed {pos}"),
+				};
+				events_.lock().unwrap().push(event);
+			});
+		let body = client.get(url).send().await.unwrap();
+		assert_eq!(body.bytes().await.unwrap(), BODY);
+		let events = events.lock().unwrap();
+		// The body may arrive in any number of chunks, so only the last of each run is compared
+		let events = (0..events.len())
+			.filter(|&i| {
+				!events[i..]
+					.iter()
+					.take(2)
+					.all(|e| e.starts_with("received"))
+			})
+			.map(|i| &*events[i])
+			.collect::<Vec<_>>();
+		assert_eq!(
+			events,
+			[
+				"connected 200 OK 0 Some(36)",
+				"received 10 Some(36)",
+				"error",
+				"resume 10 10ms",
+				"resumed 10",
+				"received 36 Some(36)",
+				"completed 36"
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn inconsistent_range() {
+		let url = serve(|i, _| {
+			if i == 0 {
+				return Response::builder()
+					.header("accept-ranges", "bytes")
+					.header("content-length", BODY.len())
+					.body(truncated(BODY, 10))
+					.unwrap();
+			}
+			Response::builder()
+				.status(206)
+				.header("content-range", format!("bytes 5-35/{}", BODY.len()))
+				.body(Body::from(&BODY[5..]))
+				.unwrap()
+		});
+		let body = super::get(url).await.unwrap();
+		let err = body
+			.bytes_stream()
+			.map_ok(|n| n.to_vec())
+			.try_concat()
+			.await
+			.unwrap_err();
+		assert!(err.is_inconsistent_range());
+		assert!(matches!(
+			std::error::Error::source(&err).unwrap().downcast_ref(),
+			Some(super::Error::InconsistentRange {
+				expected: 10,
+				received: Some(5)
+			})
+		));
+	}
+
+	#[tokio::test]
+	async fn retry_policy() {
+		// Nothing is listening on the port once the listener is dropped
+		let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+		let url = format!("http://{}/", listener.local_addr().unwrap());
+		drop(listener);
+		let policy = super::RetryPolicy::new()
+			.max_attempts(3)
+			.base_delay(Duration::from_millis(10));
+		let start = Instant::now();
+		let err = super::Client::new()
+			.retry_policy(policy)
+			.get(&url)
+			.send()
+			.await
+			.unwrap_err();
+		assert!(err.is_connect() && err.is_retries_exhausted());
+		assert!(matches!(
+			err,
+			super::Error::RetriesExhausted { attempts: 3, .. }
+		));
+		assert!(start.elapsed() < Duration::from_secs(1));
+	}
+
+	#[tokio::test]
+	async fn retry_status() {
+		let url = serve(|i, _| match i {
+			0 | 2 => Response::builder()
+				.status(if i == 0 { 503 } else { 429 })
+				// Capped at max_delay
+				.header("retry-after", if i == 0 { "86400" } else { "0" })
+				.body(Body::empty())
+				.unwrap(),
+			1 => Response::builder()
+				.header("accept-ranges", "bytes")
+				.header("content-length", BODY.len())
+				.body(truncated(BODY, 10))
+				.unwrap(),
+			_ => Response::builder()
+				.status(206)
+				.header("content-range", format!("bytes 10-35/{}", BODY.len()))
+				.body(Body::from(&BODY[10..]))
+				.unwrap(),
+		});
+		let start = Instant::now();
+		let body = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().max_delay(Duration::from_millis(10)))
+			.get(url)
+			.send()
+			.await
+			.unwrap();
+		let body = body
+			.bytes_stream()
+			.map_ok(|n| n.to_vec())
+			.try_concat()
+			.await
+			.unwrap();
+		assert_eq!(body, BODY);
+		assert!(start.elapsed() < Duration::from_secs(1));
+	}
+
+	#[tokio::test]
+	async fn download_to_file() {
+		let url = serve(|_, req| {
+			assert_eq!(req.headers()["range"], "bytes=10-");
+			assert_eq!(req.headers()["if-range"], "\"a\"");
+			Response::builder()
+				.status(206)
+				.header("content-range", format!("bytes 10-35/{}", BODY.len()))
+				.header("etag", "\"a\"")
+				.body(Body::from(&BODY[10..]))
+				.unwrap()
+		});
+		let dir = std::env::temp_dir().join(format!("reqwest_resume_{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("download");
+		// As left by a previous process that was killed 10 bytes in
+		std::fs::write(dir.join("download.part"), &BODY[..12]).unwrap();
+		let meta = format!("url {url}\netag \"a\"\nlength {}\nwritten 10\n", BODY.len());
+		std::fs::write(dir.join("download.part.meta"), meta).unwrap();
+		let len = super::Client::new()
+			.download_to_file(url, &path)
+			.await
+			.unwrap();
+		assert_eq!(len, BODY.len() as u64);
+		assert_eq!(std::fs::read(&path).unwrap(), BODY);
+		assert!(!dir.join("download.part.meta").exists());
+		// As left by a previous process that was killed after writing all of it, but before moving it into place
+		let url = serve(|_, req| {
+			assert_eq!(req.headers()["range"], format!("bytes={}-", BODY.len()));
+			Response::builder()
+				.status(416)
+				.header("content-range", format!("bytes */{}", BODY.len()))
+				.body(Body::empty())
+				.unwrap()
+		});
+		std::fs::write(dir.join("download.part"), BODY).unwrap();
+		let meta = format!("url {url}\netag \"a\"\nwritten {}\n", BODY.len());
+		std::fs::write(dir.join("download.part.meta"), meta).unwrap();
+		let len = super::Client::new()
+			.download_to_file(url, &path)
+			.await
+			.unwrap();
+		assert_eq!(len, BODY.len() as u64);
+		assert_eq!(std::fs::read(&path).unwrap(), BODY);
+		assert!(!dir.join("download.part").exists());
+		// As left by a previous process, but the resource has since become shorter than what was written
+		let url = serve(|_, req| match req.headers().get("range") {
+			Some(range) => {
+				assert_eq!(range, "bytes=40-");
+				Response::builder()
+					.status(416)
+					.header("content-range", format!("bytes */{}", BODY.len()))
+					.body(Body::empty())
+					.unwrap()
+			}
+			None => Response::new(Body::from(BODY)),
+		});
+		std::fs::write(dir.join("download.part"), [0; 40]).unwrap();
+		std::fs::write(
+			dir.join("download.part.meta"),
+			format!("url {url}\nwritten 40\n"),
+		)
+		.unwrap();
+		let len = super::Client::new()
+			.download_to_file(url, &path)
+			.await
+			.unwrap();
+		assert_eq!(len, BODY.len() as u64);
+		assert_eq!(std::fs::read(&path).unwrap(), BODY);
+		assert!(!dir.join("download.part.meta").exists());
+		// An error page isn't downloaded
+		let url = serve(|_, _| {
+			Response::builder()
+				.status(404)
+				.body(Body::from("not found"))
+				.unwrap()
+		});
+		let path = dir.join("missing");
+		let err = super::Client::new()
+			.download_to_file(url, &path)
+			.await
+			.unwrap_err();
+		assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+		assert!(!path.exists());
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[tokio::test]
+	async fn segmented() {
+		let url = serve(|i, req| {
+			if i == 1 {
+				// Fail the first attempt at the second segment partway through
+				return Response::builder()
+					.status(206)
+					.header("content-range", format!("bytes 10-19/{}", BODY.len()))
+					.body(truncated(&BODY[10..20], 5))
+					.unwrap();
+			}
+			partial(BODY, req)
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = client
+			.segmented(url.clone())
+			.segment_size(10)
+			.concurrency(1)
+			.bytes_stream()
+			.map_ok(|n| n.to_vec())
+			.try_concat()
+			.await
+			.unwrap();
+		assert_eq!(body, BODY);
+		// The body is smaller than one segment
+		let body = client.segmented(url.clone()).bytes_stream();
+		let body = body.map_ok(|n| n.to_vec()).try_concat().await.unwrap();
+		assert_eq!(body, BODY);
+		let path =
+			std::env::temp_dir().join(format!("reqwest_resume_segmented_{}", std::process::id()));
+		let len = client
+			.segmented(url)
+			.segment_size(7)
+			.download_to_file(&path)
+			.await
+			.unwrap();
+		assert_eq!(len, BODY.len() as u64);
+		assert_eq!(std::fs::read(&path).unwrap(), BODY);
+		assert!(!path.with_extension("part").exists());
+		std::fs::remove_file(&path).unwrap();
+		// An error page isn't downloaded
+		let url = serve(|_, _| {
+			Response::builder()
+				.status(404)
+				.body(Body::from("not found"))
+				.unwrap()
+		});
+		let err = client
+			.segmented(url)
+			.download_to_file(&path)
+			.await
+			.unwrap_err();
+		assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+		assert!(!path.exists());
+		// The resource changes after the first segment, with only a weak ETag to tell
+		let url = serve(|i, req| {
+			let mut response = partial(BODY, req);
+			let etag = if i == 0 { "W/\"a\"" } else { "W/\"b\"" };
+			let _ = response.headers_mut().insert("etag", etag.parse().unwrap());
+			response
+		});
+		let err = client
+			.segmented(url)
+			.segment_size(10)
+			.bytes_stream()
+			.try_collect::<Vec<_>>()
+			.await
+			.unwrap_err();
+		assert!(err.is_resource_changed());
+	}
+
+	#[tokio::test]
+	async fn response() {
+		let url = serve(|_, _| {
+			Response::builder()
+				.header("content-type", "text/plain; charset=iso-8859-1")
+				.body(Body::from(&b"caf\xe9"[..]))
+				.unwrap()
+		});
+		let response = super::get(url.clone()).await.unwrap();
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			response.headers()["content-type"],
+			"text/plain; charset=iso-8859-1"
+		);
+		assert_eq!(response.content_length(), Some(4));
+		assert_eq!(response.url(), &url);
+		assert!(response.remote_addr().is_some());
+		assert_eq!(response.text().await.unwrap(), "café");
+		let mut response = super::get(url).await.unwrap();
+		assert_eq!(response.chunk().await.unwrap().unwrap(), &b"caf\xe9"[..]);
+		assert!(response.chunk().await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn into_async_read() {
+		use futures::AsyncReadExt;
+		let url = serve(flaky(BODY));
+		let mut body = super::get(url).await.unwrap().into_async_read();
+		let mut buf = [0; 4];
+		body.read_exact(&mut buf).await.unwrap();
+		assert_eq!(body.position(), 4);
+		let mut rest = Vec::new();
+		let _ = body.read_to_end(&mut rest).await.unwrap();
+		assert_eq!([&buf[..], &rest].concat(), BODY);
+		// The server doesn't support ranges, so the body can't be resumed, and every read fails
+		let url = serve(|_, _| {
+			Response::builder()
+				.header("content-length", BODY.len())
+				.body(truncated(BODY, 10))
+				.unwrap()
+		});
+		let mut body = super::get(url).await.unwrap().into_async_read();
+		let mut rest = Vec::new();
+		let err = body.read_to_end(&mut rest).await.unwrap_err();
+		assert_eq!(rest, &BODY[..10]);
+		let again = body.read_to_end(&mut rest).await.unwrap_err();
+		assert_eq!(
+			(again.kind(), again.to_string()),
+			(err.kind(), err.to_string())
+		);
+	}
+
+	#[tokio::test]
+	async fn range() {
+		let url = serve(|i, req| {
+			if i == 0 {
+				// Fail the first attempt at the range partway through
+				return Response::builder()
+					.status(206)
+					.header("accept-ranges", "bytes")
+					.header("content-range", format!("bytes 10-19/{}", BODY.len()))
+					.body(truncated(&BODY[10..20], 4))
+					.unwrap();
+			}
+			partial(BODY, req)
+		});
+		let client = super::Client::new()
+			.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+		let body = |mut request: super::RequestBuilder| async move {
+			request.send().await.unwrap().bytes().await.unwrap()
+		};
+		assert_eq!(
+			body(client.get(url.clone()).range(10..20)).await,
+			&BODY[10..20]
+		);
+		assert_eq!(body(client.get(url.clone()).range(30..)).await, &BODY[30..]);
+		assert_eq!(body(client.get(url.clone()).range(..=2)).await, &BODY[..3]);
+		assert_eq!(body(client.get(url.clone()).suffix(4)).await, &BODY[32..]);
+		assert_eq!(body(client.get(url.clone()).suffix(100)).await, BODY);
+		// The range extends past the end of the body
+		assert_eq!(body(client.get(url.clone()).range(0..100)).await, BODY);
+		assert_eq!(
+			body(client.get(url.clone()).range(30..100)).await,
+			&BODY[30..]
+		);
+		assert_eq!(body(client.get(url).range(..=u64::MAX)).await, BODY);
+		// The server ignores the Range header
+		let url = serve(|_, _| Response::new(Body::from(BODY)));
+		assert_eq!(body(client.get(url.clone()).range(5..8)).await, &BODY[5..8]);
+		assert_eq!(body(client.get(url).suffix(4)).await, &BODY[32..]);
+		// An error page isn't taken to be the whole body
+		let url = serve(|_, _| {
+			Response::builder()
+				.status(404)
+				.body(Body::from("not found"))
+				.unwrap()
+		});
+		let err = client.get(url).range(5..).send().await.unwrap_err();
+		assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+	}
+
+	#[tokio::test]
+	async fn remote_file() {
+		use futures::{AsyncReadExt, AsyncSeekExt};
+		use std::io::SeekFrom;
+		let url = serve(|i, req| {
+			if i == 0 {
+				// Opening only requests the first byte
+				assert_eq!(req.headers()["range"], "bytes=0-0");
+			}
+			partial(BODY, req)
+		});
+		let file = super::Client::new().remote_file(url).await.unwrap();
+		let mut file = file.window(8);
+		assert_eq!(file.len(), Some(BODY.len() as u64));
+		let mut buf = [0; 4];
+		file.read_exact(&mut buf).await.unwrap();
+		assert_eq!(&buf, b"0123");
+		assert_eq!(file.seek(SeekFrom::End(-4)).await.unwrap(), 32);
+		file.read_exact(&mut buf).await.unwrap();
+		assert_eq!(&buf, b"wxyz");
+		assert_eq!(file.read(&mut buf).await.unwrap(), 0);
+		assert_eq!(file.seek(SeekFrom::Start(6)).await.unwrap(), 6);
+		let mut rest = Vec::new();
+		let _ = file.read_to_end(&mut rest).await.unwrap();
+		assert_eq!(rest, &BODY[6..]);
+		assert!(file.seek(SeekFrom::Current(-100)).await.is_err());
+		// The server ignores the Range header
+		let url = serve(|_, _| Response::new(Body::from(BODY)));
+		let err = super::Client::new().remote_file(url).await.unwrap_err();
+		assert!(err.is_ranges_unsupported());
+	}
+
+	#[allow(clippy::io_other_error, clippy::uninlined_format_args)]
 	#[tokio::test]
 	async fn dl_s3() {
 		// Requests to large files on S3 regularly time out or close when made from slower connections. This test is fairly meaningless from fast connections. TODO
@@ -238,7 +2529,7 @@ mod test {
 			.map(|url| {
 				tokio::spawn(async move {
 					println!("{}", url);
-					let body = super::get(url.parse().unwrap()).await.unwrap();
+					let body = super::get(&url).await.unwrap();
 					let body = body
 						.bytes_stream()
 						.map_err(|e| io::Error::new(io::ErrorKind::Other, e));
@@ -259,4 +2550,40 @@ mod test {
 			.collect::<Result<(), _>>()
 			.unwrap();
 	}
+
+	#[cfg(feature = "blocking")]
+	#[tokio::test]
+	async fn blocking() {
+		use std::io::Read;
+		let url = serve(flaky(BODY));
+		// The server doesn't support ranges, so the body can't be resumed
+		let unresumable = serve(|_, _| {
+			Response::builder()
+				.header("content-length", BODY.len())
+				.body(truncated(BODY, 10))
+				.unwrap()
+		});
+		// The blocking API can't be used within the runtime serving the body, so is used from another thread
+		let (sender, receiver) = futures::channel::oneshot::channel();
+		let _ = std::thread::spawn(move || {
+			let client = super::blocking::Client::new()
+				.retry_policy(super::RetryPolicy::new().base_delay(Duration::from_millis(10)));
+			// Every read after the body fails fails too
+			let mut body = client.get(unresumable).send().unwrap();
+			let mut buf = Vec::new();
+			let err = body.read_to_end(&mut buf).unwrap_err();
+			let again = body.read_to_end(&mut buf).unwrap_err();
+			assert_eq!(
+				(again.kind(), again.to_string()),
+				(err.kind(), err.to_string())
+			);
+			let mut body = client.get(url).send().unwrap();
+			let mut buf = Vec::new();
+			let _ = body.read_to_end(&mut buf).unwrap();
+			sender.send((buf, body.stats())).unwrap();
+		});
+		let (body, stats) = receiver.await.unwrap();
+		assert_eq!(body, BODY);
+		assert_eq!(stats.resumes, 1);
+	}
 }
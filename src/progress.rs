@@ -0,0 +1,94 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::{Client, Error, RequestBuilder, Stats};
+
+impl Client {
+	/// Set a function to be called with each [`Event`] in the progress of requests made with this `Client`.
+	pub fn observer<F>(mut self, observer: F) -> Self
+	where
+		F: Fn(&Event) + Send + Sync + 'static,
+	{
+		self.observer = Observer(Some(Arc::new(observer)));
+		self
+	}
+}
+impl RequestBuilder {
+	/// Set a function to be called with each [`Event`] in the progress of this request, overriding that of the [`Client`].
+	pub fn observer<F>(mut self, observer: F) -> Self
+	where
+		F: Fn(&Event) + Send + Sync + 'static,
+	{
+		self.observer = Observer(Some(Arc::new(observer)));
+		self
+	}
+}
+
+/// An event in the progress of a request, passed to the function set with [`Client::observer()`] or [`RequestBuilder::observer()`].
+///
+/// Offsets are of the whole representation, so a body requested with a range starts at the start of the range.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Event<'a> {
+	/// The initial response was received.
+	Connected {
+		/// The status of the response.
+		status: reqwest::StatusCode,
+		/// The offset the body starts at.
+		pos: u64,
+		/// The length of the whole representation, if known.
+		total: Option<u64>,
+	},
+	/// A chunk of the body was received.
+	Received {
+		/// The offset the body has reached.
+		pos: u64,
+		/// The length of the whole representation, if known.
+		total: Option<u64>,
+	},
+	/// An attempt failed. If it's to be retried, this is followed by [`Event::RetryScheduled`] or [`Event::ResumeScheduled`].
+	Error {
+		/// The error the attempt failed with.
+		error: &'a Error,
+	},
+	/// The initial request is to be retried.
+	RetryScheduled {
+		/// The delay before the retry.
+		delay: Duration,
+	},
+	/// The body is to be resumed.
+	ResumeScheduled {
+		/// The offset the body is to be resumed from.
+		pos: u64,
+		/// The delay before the resume.
+		delay: Duration,
+	},
+	/// The body was resumed.
+	Resumed {
+		/// The offset the body was resumed from.
+		pos: u64,
+	},
+	/// The body was received in full.
+	Completed {
+		/// The offset the body ended at.
+		pos: u64,
+		/// The final statistics of the request and its body.
+		stats: Stats,
+	},
+}
+
+/// The function, if any, to be called with each [`Event`].
+#[derive(Clone, Default)]
+pub(crate) struct Observer(Option<Arc<ObserverFn>>);
+type ObserverFn = dyn Fn(&Event) + Send + Sync;
+impl Observer {
+	pub(crate) fn emit(&self, event: &Event) {
+		if let Some(observer) = &self.0 {
+			observer(event);
+		}
+	}
+}
+impl fmt::Debug for Observer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_tuple("Observer").field(&self.0.is_some()).finish()
+	}
+}
@@ -0,0 +1,118 @@
+use bytes::{Buf, Bytes};
+use futures::{ready, Stream};
+use std::{
+	io, pin::Pin, task::{Context, Poll}
+};
+
+use super::{Decoder, Response, Stats};
+
+impl Response {
+	/// Convert the response into a reader of the body, implementing both the `futures` and `tokio` `AsyncRead` and `AsyncBufRead` traits.
+	///
+	/// See [`BodyReader`].
+	pub fn into_async_read(self) -> BodyReader {
+		BodyReader {
+			decoder: self.decoder,
+			chunk: Bytes::new(),
+			failed: None,
+		}
+	}
+}
+
+/// A reader of a resumable response body.
+///
+/// Errors are returned as an [`io::Error`] wrapping the [`reqwest_resume::Error`](super::Error), retrievable with [`io::Error::into_inner()`] and downcasting. The body has already been retried per the [`RetryPolicy`](super::RetryPolicy) by then, so it can't be read any further: every later read fails with an error of the same kind and message, rather than ending as though the body were complete.
+#[derive(Debug)]
+pub struct BodyReader {
+	decoder: Decoder,
+	chunk: Bytes,
+	failed: Option<Failed>,
+}
+impl BodyReader {
+	/// The offset in the resource of the next byte to be read.
+	///
+	/// If the body is being [decompressed](super::RequestBuilder::decompress), this is instead the offset in the compressed resource that's been decompressed up to.
+	pub fn position(&self) -> u64 {
+		if self.decoder.decompressor.is_some() {
+			return self.decoder.pos;
+		}
+		self.decoder.pos - self.chunk.len() as u64
+	}
+	/// A snapshot of the [`Stats`] of this body so far.
+	pub fn stats(&self) -> Stats {
+		self.decoder.tracker.snapshot()
+	}
+	fn poll_chunk(&mut self, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+		if let Some(failed) = &self.failed {
+			return Poll::Ready(Err(failed.error()));
+		}
+		while self.chunk.is_empty() {
+			match ready!(Pin::new(&mut self.decoder).poll_next(cx)) {
+				Some(Ok(chunk)) => self.chunk = chunk,
+				Some(Err(err)) => {
+					let err = io::Error::from(err);
+					self.failed = Some(Failed::new(&err));
+					return Poll::Ready(Err(err));
+				}
+				None => break,
+			}
+		}
+		Poll::Ready(Ok(&self.chunk))
+	}
+	fn read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+		let chunk = ready!(self.poll_chunk(cx))?;
+		let n = chunk.len().min(buf.len());
+		buf[..n].copy_from_slice(&chunk[..n]);
+		self.chunk.advance(n);
+		Poll::Ready(Ok(n))
+	}
+}
+
+/// The error a reader of a body failed with, to fail every later read with too.
+#[derive(Debug)]
+pub(crate) struct Failed {
+	kind: io::ErrorKind,
+	message: String,
+}
+impl Failed {
+	pub(crate) fn new(err: &io::Error) -> Self {
+		Self {
+			kind: err.kind(),
+			message: err.to_string(),
+		}
+	}
+	pub(crate) fn error(&self) -> io::Error {
+		io::Error::new(self.kind, self.message.clone())
+	}
+}
+
+impl futures::io::AsyncRead for BodyReader {
+	fn poll_read(
+		self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.get_mut().read(cx, buf)
+	}
+}
+impl futures::io::AsyncBufRead for BodyReader {
+	fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+		self.get_mut().poll_chunk(cx)
+	}
+	fn consume(self: Pin<&mut Self>, amt: usize) {
+		self.get_mut().chunk.advance(amt);
+	}
+}
+impl tokio::io::AsyncRead for BodyReader {
+	fn poll_read(
+		self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.get_mut().read(cx, buf)
+	}
+}
+impl tokio::io::AsyncBufRead for BodyReader {
+	fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<&[u8]>> {
+		self.get_mut().poll_chunk(cx)
+	}
+	fn consume(self: Pin<&mut Self>, amt: usize) {
+		self.get_mut().chunk.advance(amt);
+	}
+}
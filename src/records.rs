@@ -0,0 +1,82 @@
+use bytes::{Bytes, BytesMut};
+use futures::{ready, Stream, StreamExt};
+use std::{
+	io, pin::Pin, task::{Context, Poll}
+};
+
+use super::{Decoder, Response, Result};
+
+impl Response {
+	/// Convert the response into a `Stream` of the lines of the body, without their `\n` or `\r\n` terminators.
+	///
+	/// Lines are split from the resumed body, so a resume partway through a line neither truncates nor repeats it. A line that isn't valid UTF-8 is yielded as an [`Error::Io`](super::Error::Io) of kind [`InvalidData`](io::ErrorKind::InvalidData).
+	pub fn lines(self) -> impl Stream<Item = Result<String>> + Send {
+		self.records(b'\n').map(|line| {
+			let mut line = line?;
+			if line.ends_with(b"\r") {
+				line.truncate(line.len() - 1);
+			}
+			String::from_utf8(line.to_vec())
+				.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err).into())
+		})
+	}
+	/// Convert the response into a `Stream` of the records of the body separated by `delimiter`, without it.
+	///
+	/// A final record without a trailing delimiter is yielded too. See [`Response::lines()`].
+	pub fn records(self, delimiter: u8) -> impl Stream<Item = Result<Bytes>> + Send {
+		Records {
+			decoder: self.decoder,
+			delimiter,
+			buf: BytesMut::new(),
+			scanned: 0,
+			done: false,
+		}
+	}
+}
+
+struct Records {
+	decoder: Decoder,
+	delimiter: u8,
+	buf: BytesMut,
+	/// The length of the start of `buf` known not to contain the delimiter.
+	scanned: usize,
+	done: bool,
+}
+impl Stream for Records {
+	type Item = Result<Bytes>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+		loop {
+			if let Some(i) = this.buf[this.scanned..]
+				.iter()
+				.position(|&byte| byte == this.delimiter)
+			{
+				let mut record = this.buf.split_to(this.scanned + i + 1);
+				record.truncate(record.len() - 1);
+				this.scanned = 0;
+				return Poll::Ready(Some(Ok(record.freeze())));
+			}
+			this.scanned = this.buf.len();
+			if this.done {
+				this.scanned = 0;
+				let record = this.buf.split();
+				return Poll::Ready(if record.is_empty() {
+					None
+				} else {
+					Some(Ok(record.freeze()))
+				});
+			}
+			match ready!(Pin::new(&mut this.decoder).poll_next(cx)) {
+				Some(Ok(chunk)) => this.buf.extend_from_slice(&chunk),
+				Some(Err(err)) => {
+					// The partial record can't be completed
+					this.done = true;
+					this.buf.clear();
+					return Poll::Ready(Some(Err(err)));
+				}
+				None => this.done = true,
+			}
+		}
+	}
+}
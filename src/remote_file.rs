@@ -0,0 +1,209 @@
+use futures::{future::BoxFuture, ready, FutureExt};
+use std::{
+	convert::TryFrom, fmt, future::Future, io::{self, SeekFrom}, pin::Pin, task::{Context, Poll}
+};
+
+use super::{BodyReader, Client, Error, Response, Result, Validator};
+
+/// The default number of bytes requested at a time.
+const WINDOW: u64 = 1024 * 1024;
+
+impl Client {
+	/// Open a URL for random access, reading it with `Range` requests.
+	///
+	/// See [`RemoteFile`].
+	pub fn remote_file<U>(&self, url: U) -> impl Future<Output = Result<RemoteFile>> + Send
+	where
+		U: reqwest::IntoUrl,
+	{
+		let (client, url) = (self.clone(), url.into_url());
+		async move {
+			let url = url?;
+			// Only the first byte is requested, to learn the length and validators without fetching a window that might not be read
+			let response = client
+				.get(url.clone())
+				.byte_range(0, Some(1))
+				.send()
+				.await?;
+			let response = ranged(response)?;
+			Ok(RemoteFile {
+				client,
+				url,
+				validator: response.decoder.validator.clone(),
+				length: response.total_length(),
+				window: WINDOW,
+				pos: 0,
+				state: State::Idle,
+			})
+		}
+	}
+}
+
+/// A remote resource that can be read from arbitrary offsets, implementing both the `futures` and `tokio` `AsyncRead` and `AsyncSeek` traits.
+///
+/// Reads are served by requesting a window of bytes starting at the current position; seeking elsewhere drops the current request and the next read makes a new one. Each window is resumed if its connection fails, and the resource is checked not to have changed since it was opened, failing with [`Error::ResourceChanged`] if it has.
+///
+/// The server must support range requests. If it responds to one with the whole resource instead, opening or reading fails with [`Error::RangesUnsupported`], rather than downloading all of it for every window.
+pub struct RemoteFile {
+	client: Client,
+	url: reqwest::Url,
+	validator: Validator,
+	length: Option<u64>,
+	window: u64,
+	pos: u64,
+	state: State,
+}
+#[allow(clippy::large_enum_variant)]
+enum State {
+	Idle,
+	Requesting(BoxFuture<'static, Result<Response>>),
+	Reading(BodyReader),
+}
+impl RemoteFile {
+	/// Set the number of bytes requested at a time. Defaults to 1 MiB.
+	///
+	/// # Panics
+	///
+	/// Panics if `window` is zero.
+	pub fn window(mut self, window: u64) -> Self {
+		assert_ne!(window, 0, "window must be non-zero");
+		self.window = window;
+		self
+	}
+	/// The length of the resource, if the server reported it.
+	pub fn len(&self) -> Option<u64> {
+		self.length
+	}
+	/// Whether the resource is known to be empty.
+	pub fn is_empty(&self) -> bool {
+		self.length == Some(0)
+	}
+	/// The offset of the next byte to be read.
+	pub fn position(&self) -> u64 {
+		self.pos
+	}
+	fn request(&self) -> BoxFuture<'static, Result<Response>> {
+		let pos = self.pos;
+		// The window is only bounded if the length is known, so the end of a window can be told apart from the end of the resource
+		let end = self.length.map(|length| (pos + self.window).min(length));
+		self.client
+			.get(self.url.clone())
+			.send_range(pos, end, self.validator.clone())
+			.map(|response| response.and_then(ranged))
+			.boxed()
+	}
+	fn read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+		loop {
+			match &mut self.state {
+				State::Idle => {
+					if buf.is_empty() || matches!(self.length, Some(length) if self.pos >= length) {
+						return Poll::Ready(Ok(0));
+					}
+					self.state = State::Requesting(self.request());
+				}
+				State::Requesting(request) => {
+					let response = ready!(request.as_mut().poll(cx));
+					self.state = State::Idle;
+					self.state = State::Reading(response?.into_async_read());
+				}
+				State::Reading(reader) => {
+					let n = ready!(futures::io::AsyncRead::poll_read(Pin::new(reader), cx, buf))?;
+					if n == 0 && self.length.is_some() {
+						// The end of the window
+						self.state = State::Idle;
+						continue;
+					}
+					self.pos += n as u64;
+					return Poll::Ready(Ok(n));
+				}
+			}
+		}
+	}
+	fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+		let pos = match pos {
+			SeekFrom::Start(pos) => Some(pos),
+			SeekFrom::Current(offset) => offset_by(self.pos, offset),
+			SeekFrom::End(offset) => {
+				let length = self.length.ok_or_else(|| {
+					io::Error::new(
+						io::ErrorKind::InvalidInput,
+						"can't seek from the end of a resource of unknown length",
+					)
+				})?;
+				offset_by(length, offset)
+			}
+		}
+		.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"invalid seek to a negative or overflowing position",
+			)
+		})?;
+		if pos != self.pos {
+			self.pos = pos;
+			self.state = State::Idle;
+		}
+		Ok(pos)
+	}
+}
+impl fmt::Debug for RemoteFile {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("RemoteFile")
+			.field("url", &self.url)
+			.field("length", &self.length)
+			.field("window", &self.window)
+			.field("pos", &self.pos)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Fail unless `response` is of the range requested, rather than of the whole resource.
+fn ranged(response: Response) -> Result<Response> {
+	match response.status() {
+		reqwest::StatusCode::PARTIAL_CONTENT => Ok(response),
+		// The resource is empty, so no range of it can be satisfied
+		reqwest::StatusCode::RANGE_NOT_SATISFIABLE if response.total_length() == Some(0) => {
+			Ok(response)
+		}
+		_ => {
+			let _ = response.success()?;
+			Err(Error::RangesUnsupported)
+		}
+	}
+}
+
+fn offset_by(pos: u64, offset: i64) -> Option<u64> {
+	if offset >= 0 {
+		pos.checked_add(u64::try_from(offset).ok()?)
+	} else {
+		pos.checked_sub(offset.unsigned_abs())
+	}
+}
+
+impl futures::io::AsyncRead for RemoteFile {
+	fn poll_read(
+		self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.get_mut().read(cx, buf)
+	}
+}
+impl futures::io::AsyncSeek for RemoteFile {
+	fn poll_seek(self: Pin<&mut Self>, _cx: &mut Context, pos: SeekFrom) -> Poll<io::Result<u64>> {
+		Poll::Ready(self.get_mut().seek(pos))
+	}
+}
+impl tokio::io::AsyncRead for RemoteFile {
+	fn poll_read(
+		self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		self.get_mut().read(cx, buf)
+	}
+}
+impl tokio::io::AsyncSeek for RemoteFile {
+	fn start_seek(self: Pin<&mut Self>, _cx: &mut Context, pos: SeekFrom) -> Poll<io::Result<()>> {
+		Poll::Ready(self.get_mut().seek(pos).map(drop))
+	}
+	fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<u64>> {
+		Poll::Ready(Ok(self.pos))
+	}
+}
@@ -0,0 +1,257 @@
+use bytes::{Bytes, BytesMut};
+use futures::{future, stream, Stream, StreamExt, TryFutureExt, TryStreamExt};
+use std::{
+	future::Future, io::SeekFrom, path::{Path, PathBuf}
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use super::{download::with_suffix, Client, Error, RequestBuilder, Response, Result, Validator};
+
+impl Client {
+	/// Download a URL in segments, fetched concurrently with a `Range` request each.
+	///
+	/// See [`Segmented`].
+	pub fn segmented<U>(&self, url: U) -> Segmented
+	where
+		U: reqwest::IntoUrl,
+	{
+		Segmented {
+			client: self.clone(),
+			url: url.into_url(),
+			segment_size: 8 * 1024 * 1024,
+			concurrency: 4,
+		}
+	}
+}
+
+/// A builder for a download that's split into segments, fetched concurrently with a `Range` request each.
+///
+/// Each segment is resumed independently if its connection fails. A response with an unsuccessful status fails the download. The first segment is requested up front: its `Content-Range` gives the total length, and its validators are used to ensure the remaining segments are of the same representation. If the server doesn't respond with a range, the body is downloaded over a single connection instead.
+#[derive(Debug)]
+pub struct Segmented {
+	client: Client,
+	url: reqwest::Result<reqwest::Url>,
+	segment_size: u64,
+	concurrency: usize,
+}
+impl Segmented {
+	/// Set the size of each segment. Defaults to 8 MiB.
+	///
+	/// # Panics
+	///
+	/// Panics if `segment_size` is zero.
+	pub fn segment_size(mut self, segment_size: u64) -> Self {
+		assert_ne!(segment_size, 0, "segment_size must be non-zero");
+		self.segment_size = segment_size;
+		self
+	}
+	/// Set the maximum number of segments fetched concurrently. Defaults to 4.
+	///
+	/// # Panics
+	///
+	/// Panics if `concurrency` is zero.
+	pub fn concurrency(mut self, concurrency: usize) -> Self {
+		assert_ne!(concurrency, 0, "concurrency must be non-zero");
+		self.concurrency = concurrency;
+		self
+	}
+	/// Reassemble the segments in order into a `Stream` of `Bytes`.
+	///
+	/// Up to `concurrency` segments are buffered in memory at a time.
+	pub fn bytes_stream(self) -> impl Stream<Item = Result<Bytes>> + Send {
+		let concurrency = self.concurrency;
+		self.segments()
+			.map_ok(move |segments| match segments {
+				Segments::Ranged(segments) => stream::iter(segments)
+					.map(Segment::bytes)
+					.buffered(concurrency)
+					.boxed(),
+				Segments::Whole(response) => response.bytes_stream().boxed(),
+			})
+			.try_flatten_stream()
+	}
+	/// Download the segments into a file, each being written at its offset as it arrives.
+	///
+	/// The body is written to `<path>.part`, which is renamed to `path` once complete, so a failure doesn't leave `path` truncated or with holes.
+	///
+	/// Resolves to the length of the file.
+	pub fn download_to_file<P>(self, path: P) -> impl Future<Output = Result<u64>> + Send
+	where
+		P: AsRef<Path>,
+	{
+		let path = path.as_ref().to_owned();
+		let concurrency = self.concurrency;
+		let segments = self.segments();
+		async move {
+			let segments = segments.await?;
+			let part = with_suffix(&path, ".part");
+			let mut file = fs::File::create(&part).await?;
+			let length = match segments {
+				Segments::Ranged(segments) => {
+					let length = segments.length;
+					file.set_len(length).await?;
+					stream::iter(segments)
+						.map(|segment| segment.write(part.clone()))
+						.buffer_unordered(concurrency)
+						.try_collect::<()>()
+						.await?;
+					length
+				}
+				Segments::Whole(response) => {
+					let mut body = response.bytes_stream();
+					let mut length = 0;
+					while let Some(chunk) = body.try_next().await? {
+						file.write_all(&chunk).await?;
+						length += chunk.len() as u64;
+					}
+					length
+				}
+			};
+			file.sync_all().await?;
+			fs::rename(&part, &path).await?;
+			Ok(length)
+		}
+	}
+	/// Request the first segment, and from its response plan the rest.
+	fn segments(self) -> impl Future<Output = Result<Segments>> + Send {
+		let Self {
+			client,
+			url,
+			segment_size,
+			..
+		} = self;
+		async move {
+			let url = url?;
+			let mut first = client
+				.get(url.clone())
+				.byte_range(0, Some(segment_size))
+				.send()
+				.await?
+				.success()?;
+			if first.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+				// The server ignored the Range header and sent the whole body
+				first.decoder.end = None;
+				return Ok(Segments::Whole(first));
+			}
+			let Some(length) = first.total_length() else {
+				// The total length is unknown, so the body can't be split
+				return Ok(Segments::Whole(client.get(url).send().await?.success()?));
+			};
+			let validator = first.decoder.validator.clone();
+			Ok(Segments::Ranged(Ranged {
+				client,
+				url,
+				validator,
+				segment_size,
+				length,
+				first: Some(first),
+				next: segment_size,
+			}))
+		}
+	}
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Segments {
+	Ranged(Ranged),
+	Whole(Response),
+}
+
+/// An iterator over the segments, the first of which has already been requested.
+struct Ranged {
+	client: Client,
+	url: reqwest::Url,
+	validator: Validator,
+	segment_size: u64,
+	length: u64,
+	first: Option<Response>,
+	next: u64,
+}
+impl Iterator for Ranged {
+	type Item = Segment;
+
+	fn next(&mut self) -> Option<Segment> {
+		if let Some(first) = self.first.take() {
+			let end = self.segment_size.min(self.length);
+			return Some(Segment {
+				start: 0,
+				end,
+				validator: self.validator.clone(),
+				request: Request::Responded(first),
+			});
+		}
+		if self.next >= self.length {
+			return None;
+		}
+		let (start, end) = (self.next, (self.next + self.segment_size).min(self.length));
+		self.next = end;
+		Some(Segment {
+			start,
+			end,
+			validator: self.validator.clone(),
+			request: Request::Unsent(self.client.get(self.url.clone())),
+		})
+	}
+}
+
+/// The bytes from `start` up to but excluding `end`.
+struct Segment {
+	start: u64,
+	end: u64,
+	validator: Validator,
+	request: Request,
+}
+/// The request for a [`Segment`], either yet to be sent or already responded to.
+#[allow(clippy::large_enum_variant)]
+enum Request {
+	Unsent(RequestBuilder),
+	Responded(Response),
+}
+impl Segment {
+	async fn response(self) -> Result<Response> {
+		match self.request {
+			Request::Unsent(request) => request
+				.send_range(self.start, Some(self.end), self.validator)
+				.await?
+				.success(),
+			Request::Responded(response) => Ok(response),
+		}
+	}
+	async fn bytes(self) -> Result<Bytes> {
+		let (start, end) = (self.start, self.end);
+		let body = self
+			.response()
+			.await?
+			.bytes_stream()
+			.try_fold(BytesMut::new(), |mut body, chunk| {
+				body.extend_from_slice(&chunk);
+				future::ok(body)
+			})
+			.await?;
+		check_length(start, end, body.len() as u64)?;
+		Ok(body.freeze())
+	}
+	async fn write(self, path: PathBuf) -> Result<()> {
+		let (start, end) = (self.start, self.end);
+		let mut file = fs::OpenOptions::new().write(true).open(&path).await?;
+		let _ = file.seek(SeekFrom::Start(start)).await?;
+		let mut body = self.response().await?.bytes_stream();
+		let mut pos = start;
+		while let Some(chunk) = body.try_next().await? {
+			file.write_all(&chunk).await?;
+			pos += chunk.len() as u64;
+		}
+		file.flush().await?;
+		check_length(start, end, pos - start)
+	}
+}
+
+fn check_length(start: u64, end: u64, received: u64) -> Result<()> {
+	if received != end - start {
+		return Err(Error::Truncated {
+			expected: end,
+			received: start + received,
+		});
+	}
+	Ok(())
+}
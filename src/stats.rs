@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+use super::Response;
+
+impl Response {
+	/// A snapshot of the [`Stats`] of this response so far.
+	pub fn stats(&self) -> Stats {
+		self.decoder.tracker.snapshot()
+	}
+}
+
+/// Statistics of a request and its body, across every retry and resume.
+///
+/// A snapshot can be taken with [`Response::stats()`] or [`BodyReader::stats()`](super::BodyReader::stats), and the final one is passed with [`Event::Completed`](super::Event::Completed). With the `metrics` feature, the final statistics are also recorded with the [`metrics`](https://docs.rs/metrics) facade once the body finishes, labelled with the host.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Stats {
+	/// The number of bytes of the body delivered.
+	pub bytes: u64,
+	/// The number of requests made, including the initial request, its retries, and resumes.
+	pub attempts: u32,
+	/// The number of times the body was resumed after failing partway through.
+	pub resumes: u32,
+	/// The total time spent waiting between attempts.
+	pub backoff: Duration,
+	/// The number of bytes received but discarded, as the server restarted from before the position resumed from.
+	pub wasted: u64,
+	/// The time since the initial request was made, until the body finished if it has.
+	pub elapsed: Duration,
+}
+impl Stats {
+	/// The average rate in bytes per second the body was delivered at, over [`elapsed`](Self::elapsed).
+	#[allow(clippy::cast_precision_loss)]
+	pub fn throughput(&self) -> f64 {
+		self.bytes as f64 / self.elapsed.as_secs_f64()
+	}
+}
+
+/// Accumulates the [`Stats`] of a request and its body.
+#[derive(Debug)]
+pub(crate) struct Tracker {
+	pub(crate) stats: Stats,
+	started: Instant,
+	finished: Option<Instant>,
+}
+impl Tracker {
+	pub(crate) fn new() -> Self {
+		Self {
+			stats: Stats {
+				bytes: 0,
+				attempts: 0,
+				resumes: 0,
+				backoff: Duration::from_secs(0),
+				wasted: 0,
+				elapsed: Duration::from_secs(0),
+			},
+			started: Instant::now(),
+			finished: None,
+		}
+	}
+	pub(crate) fn snapshot(&self) -> Stats {
+		Stats {
+			elapsed: self
+				.finished
+				.unwrap_or_else(Instant::now)
+				.duration_since(self.started),
+			..self.stats
+		}
+	}
+	/// Record that the body finished, successfully or not, returning the final [`Stats`].
+	#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+	pub(crate) fn finish(&mut self, url: &reqwest::Url) -> Stats {
+		if self.finished.is_none() {
+			self.finished = Some(Instant::now());
+			#[cfg(feature = "metrics")]
+			record(&self.snapshot(), url);
+		}
+		self.snapshot()
+	}
+}
+
+#[cfg(feature = "metrics")]
+#[allow(clippy::cast_precision_loss)]
+fn record(stats: &Stats, url: &reqwest::Url) {
+	let host = url.host_str().unwrap_or_default().to_owned();
+	metrics::counter!("reqwest_resume_bytes_total", "host" => host.clone()).increment(stats.bytes);
+	metrics::counter!("reqwest_resume_attempts_total", "host" => host.clone())
+		.increment(stats.attempts.into());
+	metrics::counter!("reqwest_resume_resumes_total", "host" => host.clone())
+		.increment(stats.resumes.into());
+	metrics::counter!("reqwest_resume_wasted_bytes_total", "host" => host.clone())
+		.increment(stats.wasted);
+	metrics::histogram!("reqwest_resume_backoff_seconds", "host" => host.clone())
+		.record(stats.backoff.as_secs_f64());
+	metrics::histogram!("reqwest_resume_throughput_bytes_per_second", "host" => host)
+		.record(stats.throughput());
+}
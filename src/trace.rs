@@ -0,0 +1,107 @@
+//! Spans and events for the `tracing` feature. Without it, [`Trace`] does nothing.
+
+use std::future::Future;
+
+#[cfg(feature = "tracing")]
+use super::Error;
+use super::Event;
+
+/// The span of a download, and of its current attempt.
+///
+/// Each attempt – the initial request, each retry of it, and each resume of the body – gets its own span, a child of the download's.
+#[derive(Debug)]
+pub(crate) struct Trace {
+	#[cfg(feature = "tracing")]
+	download: tracing::Span,
+	#[cfg(feature = "tracing")]
+	attempt: tracing::Span,
+	#[cfg(feature = "tracing")]
+	attempts: u32,
+}
+
+#[cfg(feature = "tracing")]
+impl Trace {
+	pub(crate) fn new(request: &reqwest::Request) -> Self {
+		Self {
+			download: tracing::info_span!("download", method = %request.method(), url = %request.url()),
+			attempt: tracing::Span::none(),
+			attempts: 0,
+		}
+	}
+	/// Start a new attempt at `offset`, ending the previous one.
+	pub(crate) fn attempt(&mut self, offset: u64) {
+		self.attempts += 1;
+		self.attempt = tracing::debug_span!(
+			parent: &self.download,
+			"attempt",
+			attempt = self.attempts,
+			offset,
+		);
+	}
+	/// Run `future` in the current attempt's span.
+	pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+		tracing::Instrument::instrument(future, self.attempt.clone())
+	}
+	pub(crate) fn event(&self, event: &Event) {
+		let span = &self.attempt;
+		match *event {
+			Event::Connected { status, pos, total } => {
+				tracing::debug!(parent: span, %status, offset = pos, ?total, "connected");
+			}
+			Event::Received { pos, total } => {
+				tracing::trace!(parent: span, offset = pos, ?total, "received");
+			}
+			Event::Error { error } => {
+				tracing::warn!(parent: span, kind = kind(error), %error, "attempt failed");
+			}
+			Event::RetryScheduled { delay } => {
+				tracing::info!(parent: span, ?delay, "retrying");
+			}
+			Event::ResumeScheduled { pos, delay } => {
+				tracing::info!(parent: span, offset = pos, ?delay, "resuming");
+			}
+			Event::Resumed { pos } => {
+				tracing::debug!(parent: span, offset = pos, "resumed");
+			}
+			Event::Completed { pos, stats } => {
+				tracing::debug!(parent: &self.download, offset = pos, ?stats, "completed");
+			}
+		}
+	}
+}
+
+#[cfg(not(feature = "tracing"))]
+#[allow(clippy::unused_self)]
+impl Trace {
+	pub(crate) fn new(_request: &reqwest::Request) -> Self {
+		Self {}
+	}
+	pub(crate) fn attempt(&mut self, _offset: u64) {}
+	pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+		future
+	}
+	pub(crate) fn event(&self, _event: &Event) {}
+}
+
+/// A short name for the kind of `error`, for the `kind` field.
+#[cfg(feature = "tracing")]
+fn kind(error: &Error) -> &'static str {
+	match error {
+		Error::Body { source, .. } | Error::RetriesExhausted { source, .. } => kind(source),
+		Error::Builder(_) => "builder",
+		Error::ResourceChanged => "resource_changed",
+		Error::UnexpectedStatus(_) => "unexpected_status",
+		Error::RangesUnsupported => "ranges_unsupported",
+		Error::InconsistentRange { .. } => "inconsistent_range",
+		Error::Truncated { .. } => "truncated",
+		Error::ChecksumMismatch { .. } => "checksum_mismatch",
+		Error::Io(_) => "io",
+		#[cfg(feature = "json")]
+		Error::Json(_) => "json",
+		Error::Reqwest(err) if err.is_timeout() => "timeout",
+		Error::Reqwest(err) if err.is_connect() => "connect",
+		Error::Reqwest(err) if err.is_status() => "status",
+		Error::Reqwest(err) if err.is_body() || err.is_decode() => "body",
+		Error::Reqwest(_) => "request",
+	}
+}